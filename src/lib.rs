@@ -1,35 +1,240 @@
 pub mod model;
 mod parser;
 
-use model::LefData;
+use model::{LefData, MergePolicy};
 use nom::{
     error::{convert_error, VerboseError},
     Err, IResult,
 };
 use parser::lef_parser::*;
 use std::{
+    fs,
     io::{Error, ErrorKind},
+    path::Path,
     str::FromStr,
 };
 
 impl FromStr for LefData {
     type Err = Error;
+    /// parses a single library. PROPERTY values are converted to their definition, the
+    /// ones defined in another library (usually the technology LEF) are only warnings.
+    /// Statements the declared VERSION does not allow are accepted and only reported in
+    /// `warnings` as well.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match lef_parser(s) {
-            Ok((_, u)) => Ok(u),
-            Err(Err::Error(e)) => {
-                println!("[LEFParser] `VerboseError`:\n{}", convert_error(s, e));
-                Err(Error::new(
-                    ErrorKind::InvalidData,
-                    "Invalid Technology File",
-                ))
-            }
-            _ => Err(Error::new(
+        let mut u = parse_lef(s)?;
+        let undefined = u.convert_properties().map_err(invalid_data)?;
+        u.warnings.extend(
+            undefined
+                .into_iter()
+                .map(|x| format!("property {} is not defined", x)),
+        );
+        Ok(u)
+    }
+}
+
+impl LefData {
+    /// reads and merges several LEF files, usually a technology LEF followed by cell
+    /// LEFs. Properties are checked against the definitions of the merged library.
+    pub fn from_files<P: AsRef<Path>>(files: &[P], policy: MergePolicy) -> Result<Self, Error> {
+        let mut lef: Option<LefData> = None;
+        for file in files {
+            let file = file.as_ref();
+            let mut u = parse_lef(&fs::read_to_string(file)?)?;
+            u.set_source_file(&file.display().to_string());
+            lef = Some(match lef {
+                Some(x) => x.merge(u, policy).map_err(invalid_data)?,
+                None => u,
+            });
+        }
+        let mut lef = lef.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "No LEF file"))?;
+        lef.check_properties().map_err(invalid_data)?;
+        Ok(lef)
+    }
+}
+
+fn invalid_data(e: String) -> Error {
+    Error::new(ErrorKind::InvalidData, e)
+}
+
+fn parse_lef(s: &str) -> Result<LefData, Error> {
+    match lef_parser(s) {
+        Ok((_, u)) => Ok(u),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+            println!("[LEFParser] `VerboseError`:\n{}", convert_error(s, e));
+            Err(Error::new(
                 ErrorKind::InvalidData,
                 "Invalid Technology File",
-            )),
+            ))
         }
+        _ => Err(Error::new(
+            ErrorKind::InvalidData,
+            "Invalid Technology File",
+        )),
     }
 }
 
 pub type LefRes<T, U> = IResult<T, U, VerboseError<T>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_properties() {
+        let lef = "VERSION 5.7 ;
+PROPERTYDEFINITIONS
+  LAYER thickness REAL RANGE 0 2 ;
+  MACRO cellType STRING ;
+  PIN pinWeight INTEGER ;
+  MACRO code STRING ;
+  MACRO revision STRING ;
+END PROPERTYDEFINITIONS
+LAYER metal1
+  TYPE ROUTING ;
+  PROPERTY thickness 1 ;
+END metal1
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+MACRO INV
+  CLASS CORE ;
+  ORIGIN 0 0 ;
+  FOREIGN INV 0 0 ;
+  SIZE 1.0 BY 1.4 ;
+  SYMMETRY X Y ;
+  SITE core ;
+  PIN A
+    DIRECTION INPUT ;
+    PROPERTY pinWeight 2 ;
+    PORT
+      LAYER metal1 ;
+        RECT 0.1 0.1 0.2 0.2 ;
+    END
+  END A
+  PROPERTY cellType \"inverter\" ;
+  PROPERTY code 007 revision 1.50 ;
+END INV
+END LIBRARY";
+        let data: LefData = lef.parse().unwrap();
+        // numbers of a STRING property keep their text
+        assert_eq!(
+            data.macro_[0].property[1..],
+            [
+                (
+                    "code".to_string(),
+                    model::PropValue::String("007".to_string())
+                ),
+                (
+                    "revision".to_string(),
+                    model::PropValue::String("1.50".to_string())
+                ),
+            ]
+        );
+        assert_eq!(data.layer[0].property[0].1, model::PropValue::Real(1.0));
+        assert_eq!(
+            data.macro_[0].property[0].1,
+            model::PropValue::String("inverter".to_string())
+        );
+        assert_eq!(
+            data.macro_[0].macro_pin[0].property[0].1,
+            model::PropValue::Integer(2)
+        );
+
+        assert!(lef
+            .replace("thickness 1 ;", "thickness 3 ;")
+            .parse::<LefData>()
+            .is_err());
+        assert!(lef
+            .replace("pinWeight 2 ;", "pinWeight 2.5 ;")
+            .parse::<LefData>()
+            .is_err());
+        // a property defined in another library is kept as parsed
+        let mut data: LefData = lef.replace("pinWeight 2 ;", "slack 2 ;").parse().unwrap();
+        assert_eq!(data.warnings, vec!["property slack is not defined"]);
+        assert_eq!(
+            data.macro_[0].macro_pin[0].property[0].1,
+            model::PropValue::Number("2".to_string())
+        );
+        assert!(data.check_properties().is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let tech = "VERSION 5.8 ;
+UNITS
+  DATABASE MICRONS 1000 ;
+END UNITS
+PROPERTYDEFINITIONS
+  MACRO cellType STRING ;
+  MACRO code STRING ;
+END PROPERTYDEFINITIONS
+LAYER metal1
+  TYPE ROUTING ;
+  WIDTH 0.1 ;
+END metal1
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+END LIBRARY";
+        let cells = "VERSION 5.8 ;
+MACRO INV
+  CLASS CORE ;
+  ORIGIN 0 0 ;
+  FOREIGN INV 0 0 ;
+  SIZE 1.0 BY 1.4 ;
+  SYMMETRY X Y ;
+  SITE core ;
+  PIN A
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0.1 0.1 0.2 0.2 ;
+    END
+  END A
+  PROPERTY cellType \"inverter\" code 007 ;
+END INV
+END LIBRARY";
+        let dir = std::env::temp_dir().join(format!("lef_merge_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let tech_file = dir.join("tech.lef");
+        let cells_file = dir.join("cells.lef");
+        fs::write(&tech_file, tech).unwrap();
+        fs::write(&cells_file, cells).unwrap();
+
+        // the cell LEF uses a property defined by the tech LEF only
+        let lef = LefData::from_files(&[&tech_file, &cells_file], MergePolicy::Error).unwrap();
+        assert_eq!(lef.dbu_per_micron(), 1000);
+        assert_eq!(lef.layer.len(), 1);
+        assert_eq!(
+            lef.find_macro("INV").unwrap().source_file,
+            Some(cells_file.display().to_string())
+        );
+        assert_eq!(
+            lef.find_macro("INV").unwrap().property[1].1,
+            model::PropValue::String("007".to_string())
+        );
+        assert_eq!(
+            lef.find_layer("metal1").unwrap().source_file,
+            Some(tech_file.display().to_string())
+        );
+        assert!(LefData::from_files(&[&cells_file, &cells_file], MergePolicy::Error).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let redefined = tech.replace("WIDTH 0.1 ;", "WIDTH 0.2 ;");
+        let first = parse_lef(tech).unwrap();
+        let last = parse_lef(&redefined).unwrap();
+        let lef = first.merge(last, MergePolicy::LastWins).unwrap();
+        assert_eq!(lef.layer.len(), 1);
+        assert_eq!(lef.layer[0].width, Some(0.2));
+
+        let first = parse_lef(tech).unwrap();
+        let last = parse_lef(&redefined).unwrap();
+        let lef = first.merge(last, MergePolicy::FirstWins).unwrap();
+        assert_eq!(lef.layer[0].width, Some(0.1));
+
+        let first = parse_lef(tech).unwrap();
+        let last = parse_lef(&tech.replace("1000", "2000")).unwrap();
+        assert!(first.merge(last, MergePolicy::Error).is_err());
+    }
+}
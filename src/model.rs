@@ -1,9 +1,1113 @@
+use std::collections::HashMap;
+
+pub type Rect = ((f64, f64), (f64, f64)); // (lower left, upper right)
+
 pub struct LefData {
-    pub version: f32,
+    pub version: f64,
     pub dividechar: String,
     pub busbitchar: String,
-    pub site: LefSite,
+    pub names_case_sensitive: Option<bool>,
+    pub no_wire_extension_at_pin: Option<bool>,
+    // MAXXY|EUCLIDEAN == 0|1
+    pub clearance_measure: Option<u8>,
+    pub use_min_spacing: Option<bool>, // USEMINSPACING OBS
+    pub max_via_stack: Option<(u32, Option<(String, String)>)>, // (value, RANGE bottom top)
+    pub fixed_mask: bool,
+    // 5.3 library antenna areas, replaced by the per pin ones since 5.4
+    pub antenna_input_gate_area: Option<f64>,
+    pub antenna_inout_diff_area: Option<f64>,
+    pub antenna_output_diff_area: Option<f64>,
+    // SPACING SAMENET ... END SPACING, (layer1, layer2, min spacing, STACK)
+    pub same_net_spacing: Vec<(String, String, f64, bool)>,
+    pub units: Option<LefUnits>,
+    pub manufacturing_grid: Option<f64>,
+    pub property_definitions: Vec<PropertyDefinition>,
+    pub property: Vec<(String, PropValue)>,
+    pub layer: Vec<LefLayer>,
+    pub via: Vec<LefVia>,
+    pub viarule: Vec<LefViaRule>,
+    pub nondefaultrule: Vec<NonDefaultRule>,
+    pub site: Vec<LefSite>,
     pub macro_: Vec<LefMacro>,
+    pub comments: Vec<String>, // `#` comments not in front of an object
+    pub warnings: Vec<String>, // problems that did not stop the parser
+}
+
+// how `LefData::merge` settles an object or header value defined by more than one library
+#[derive(Clone, Copy, PartialEq)]
+pub enum MergePolicy {
+    Error,
+    FirstWins,
+    LastWins,
+}
+
+impl LefData {
+    /// records `file` as the source of every top-level object that has none yet.
+    pub fn set_source_file(&mut self, file: &str) {
+        let file = Some(file.to_string());
+        for x in self.layer.iter_mut().filter(|x| x.source_file.is_none()) {
+            x.source_file = file.clone();
+        }
+        for x in self.via.iter_mut().filter(|x| x.source_file.is_none()) {
+            x.source_file = file.clone();
+        }
+        for x in self.viarule.iter_mut().filter(|x| x.source_file.is_none()) {
+            x.source_file = file.clone();
+        }
+        for x in self.nondefaultrule.iter_mut() {
+            if x.source_file.is_none() {
+                x.source_file = file.clone();
+            }
+            for via in x.via.iter_mut().filter(|x| x.source_file.is_none()) {
+                via.source_file = file.clone();
+            }
+        }
+        for x in self.site.iter_mut().filter(|x| x.source_file.is_none()) {
+            x.source_file = file.clone();
+        }
+        for x in self.macro_.iter_mut().filter(|x| x.source_file.is_none()) {
+            x.source_file = file.clone();
+        }
+    }
+
+    /// merges `other` into this library. Header values, UNITS and objects defined by
+    /// both sides are conflicts, settled by `policy`. Objects keep their original order,
+    /// a replaced object takes the place of the one it replaces.
+    pub fn merge(mut self, other: LefData, policy: MergePolicy) -> Result<LefData, String> {
+        if self.version != other.version {
+            let msg = format!("VERSION {} conflicts with {}", other.version, self.version);
+            if merge_conflict(&msg, policy)? {
+                self.version = other.version;
+            }
+        }
+        if self.busbitchar != other.busbitchar {
+            let msg = format!(
+                "BUSBITCHARS {} conflicts with {}",
+                other.busbitchar, self.busbitchar
+            );
+            if merge_conflict(&msg, policy)? {
+                self.busbitchar = other.busbitchar;
+            }
+        }
+        if self.dividechar != other.dividechar {
+            let msg = format!(
+                "DIVIDERCHAR {} conflicts with {}",
+                other.dividechar, self.dividechar
+            );
+            if merge_conflict(&msg, policy)? {
+                self.dividechar = other.dividechar;
+            }
+        }
+        if let Some(y) = other.units {
+            let conflict = self.units.as_ref().is_some_and(|x| *x != y);
+            if !conflict || merge_conflict("UNITS conflict", policy)? {
+                self.units = Some(y);
+            }
+        }
+        if let Some(y) = other.manufacturing_grid {
+            let conflict = self.manufacturing_grid.is_some_and(|x| x != y);
+            let msg = format!("MANUFACTURINGGRID {} conflict", y);
+            if !conflict || merge_conflict(&msg, policy)? {
+                self.manufacturing_grid = Some(y);
+            }
+        }
+        for x in other.property_definitions {
+            match self
+                .property_definitions
+                .iter()
+                .position(|y| y.object_type == x.object_type && y.prop_name == x.prop_name)
+            {
+                // the same property defined again by another library
+                Some(idx) if self.property_definitions[idx].prop_type == x.prop_type => {}
+                Some(idx) => {
+                    let msg = format!("property {} is defined with another type", x.prop_name);
+                    if merge_conflict(&msg, policy)? {
+                        self.property_definitions[idx] = x;
+                    }
+                }
+                None => self.property_definitions.push(x),
+            }
+        }
+        self.property.extend(other.property);
+        self.comments.extend(other.comments);
+        self.warnings.extend(other.warnings);
+        self.names_case_sensitive = self.names_case_sensitive.or(other.names_case_sensitive);
+        self.no_wire_extension_at_pin = self
+            .no_wire_extension_at_pin
+            .or(other.no_wire_extension_at_pin);
+        self.clearance_measure = self.clearance_measure.or(other.clearance_measure);
+        self.use_min_spacing = self.use_min_spacing.or(other.use_min_spacing);
+        self.max_via_stack = self.max_via_stack.take().or(other.max_via_stack);
+        self.fixed_mask |= other.fixed_mask;
+        self.antenna_input_gate_area = self
+            .antenna_input_gate_area
+            .or(other.antenna_input_gate_area);
+        self.antenna_inout_diff_area = self
+            .antenna_inout_diff_area
+            .or(other.antenna_inout_diff_area);
+        self.antenna_output_diff_area = self
+            .antenna_output_diff_area
+            .or(other.antenna_output_diff_area);
+        self.same_net_spacing.extend(other.same_net_spacing);
+        merge_objects(
+            &mut self.layer,
+            other.layer,
+            |x| (&x.layer_name, &x.source_file),
+            "LAYER",
+            policy,
+        )?;
+        merge_objects(
+            &mut self.via,
+            other.via,
+            |x| (&x.via_name, &x.source_file),
+            "VIA",
+            policy,
+        )?;
+        merge_objects(
+            &mut self.viarule,
+            other.viarule,
+            |x| (&x.rule_name, &x.source_file),
+            "VIARULE",
+            policy,
+        )?;
+        merge_objects(
+            &mut self.nondefaultrule,
+            other.nondefaultrule,
+            |x| (&x.rule_name, &x.source_file),
+            "NONDEFAULTRULE",
+            policy,
+        )?;
+        merge_objects(
+            &mut self.site,
+            other.site,
+            |x| (&x.site_name, &x.source_file),
+            "SITE",
+            policy,
+        )?;
+        merge_objects(
+            &mut self.macro_,
+            other.macro_,
+            |x| (&x.macro_name, &x.source_file),
+            "MACRO",
+            policy,
+        )?;
+        Ok(self)
+    }
+}
+
+// returns if the later definition wins
+fn merge_conflict(msg: &str, policy: MergePolicy) -> Result<bool, String> {
+    match policy {
+        MergePolicy::Error => Err(msg.to_string()),
+        MergePolicy::FirstWins => Ok(false),
+        MergePolicy::LastWins => Ok(true),
+    }
+}
+
+fn merge_objects<T>(
+    objects: &mut Vec<T>,
+    others: Vec<T>,
+    key: fn(&T) -> (&String, &Option<String>),
+    kind: &str,
+    policy: MergePolicy,
+) -> Result<(), String> {
+    let mut index: HashMap<String, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(idx, x)| (key(x).0.clone(), idx))
+        .collect();
+    for x in others {
+        match index.get(key(&x).0).copied() {
+            Some(idx) => {
+                let (name, source) = key(&x);
+                let msg = format!(
+                    "{} {} in {} is already defined in {}",
+                    kind,
+                    name,
+                    source.as_deref().unwrap_or("<unknown>"),
+                    key(&objects[idx]).1.as_deref().unwrap_or("<unknown>"),
+                );
+                if merge_conflict(&msg, policy)? {
+                    objects[idx] = x;
+                }
+            }
+            None => {
+                index.insert(key(&x).0.clone(), objects.len());
+                objects.push(x);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub struct PropertyDefinition {
+    // LIBRARY|LAYER|VIA|VIARULE|NONDEFAULTRULE|MACRO|PIN == 0|1|2|3|4|5|6
+    pub object_type: u8,
+    pub prop_name: String,
+    // INTEGER|REAL|STRING == 0|1|2
+    pub prop_type: u8,
+    pub range: Option<(f64, f64)>,
+    pub value: Option<PropValue>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropValue {
+    Integer(i32),
+    Real(f64),
+    String(String),
+    Number(String), // unquoted number as written, until a definition gives its type
+}
+
+impl PropValue {
+    /// the value as the PROPERTYDEFINITIONS type INTEGER|REAL|STRING == 0|1|2, None if
+    /// it does not fit. A number keeps its text as written when it becomes a STRING.
+    pub fn convert(&self, prop_type: u8) -> Option<PropValue> {
+        match (prop_type, self) {
+            (0, PropValue::Integer(x)) => Some(PropValue::Integer(*x)),
+            (0, PropValue::Number(x)) => x.parse().ok().map(PropValue::Integer),
+            (1, PropValue::Integer(x)) => Some(PropValue::Real(*x as f64)),
+            (1, PropValue::Real(x)) => Some(PropValue::Real(*x)),
+            (1, PropValue::Number(x)) => x.parse().ok().map(PropValue::Real),
+            (2, PropValue::Integer(x)) => Some(PropValue::String(x.to_string())),
+            (2, PropValue::Real(x)) => Some(PropValue::String(x.to_string())),
+            (2, PropValue::String(x)) | (2, PropValue::Number(x)) => {
+                Some(PropValue::String(x.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl LefData {
+    /// checks every PROPERTY value against its PROPERTYDEFINITIONS entry and converts
+    /// it to the defined type. A property without definition is an error too.
+    pub fn check_properties(&mut self) -> Result<(), String> {
+        match self.convert_properties()?.first() {
+            Some(x) => Err(format!("property {} is not defined", x)),
+            None => Ok(()),
+        }
+    }
+
+    /// converts the PROPERTY values that have a definition to the defined type.
+    /// Returns the names of the properties without definition, those are kept as parsed.
+    pub fn convert_properties(&mut self) -> Result<Vec<String>, String> {
+        let definitions = &self.property_definitions;
+        let mut undefined = Vec::new();
+        check_property_values(definitions, 0, &mut self.property, &mut undefined)?;
+        for x in self.layer.iter_mut() {
+            check_property_values(definitions, 1, &mut x.property, &mut undefined)?;
+        }
+        for x in self.via.iter_mut() {
+            check_property_values(definitions, 2, &mut x.property, &mut undefined)?;
+        }
+        for x in self.viarule.iter_mut() {
+            check_property_values(definitions, 3, &mut x.property, &mut undefined)?;
+        }
+        for x in self.nondefaultrule.iter_mut() {
+            check_property_values(definitions, 4, &mut x.property, &mut undefined)?;
+            for via in x.via.iter_mut() {
+                check_property_values(definitions, 2, &mut via.property, &mut undefined)?;
+            }
+        }
+        for x in self.macro_.iter_mut() {
+            check_property_values(definitions, 5, &mut x.property, &mut undefined)?;
+            for pin in x.macro_pin.iter_mut() {
+                check_property_values(definitions, 6, &mut pin.property, &mut undefined)?;
+            }
+        }
+        Ok(undefined)
+    }
+}
+
+fn check_property_values(
+    definitions: &[PropertyDefinition],
+    object_type: u8,
+    values: &mut [(String, PropValue)],
+    undefined: &mut Vec<String>,
+) -> Result<(), String> {
+    for (name, value) in values.iter_mut() {
+        let definition = match definitions
+            .iter()
+            .find(|x| x.object_type == object_type && x.prop_name == *name)
+        {
+            Some(x) => x,
+            None => {
+                undefined.push(name.clone());
+                continue;
+            }
+        };
+        let typed = value
+            .convert(definition.prop_type)
+            .ok_or_else(|| format!("property {} has a value of the wrong type", name))?;
+        let number = match typed {
+            PropValue::Integer(x) => Some(x as f64),
+            PropValue::Real(x) => Some(x),
+            PropValue::String(_) | PropValue::Number(_) => None,
+        };
+        if let (Some(x), Some((min, max))) = (number, definition.range) {
+            if x < min || x > max {
+                return Err(format!("property {} is out of range", name));
+            }
+        }
+        *value = typed;
+    }
+    Ok(())
+}
+
+// rule held by a LEF58_* string property
+pub enum Lef58Rule {
+    Spacing(Lef58Spacing),
+    CutClass(Lef58CutClass),
+    Type(String),
+    EolKeepout(Lef58EolKeepout),
+    CornerSpacing(Lef58CornerSpacing),
+    EdgeType(Lef58EdgeType),
+    Raw((String, String)), // (prop_name, rule text) for rules that are not decoded
+}
+
+pub struct Lef58Spacing {
+    pub min_spacing: f64,
+    pub center_to_center: bool,
+    pub same_net: bool,
+    pub same_mask: bool,
+    pub layer_name: Option<String>,
+    pub cut_class: Option<String>,
+    pub end_of_line: Option<EndOfLineSpacing>,
+}
+
+pub struct Lef58CutClass {
+    pub class_name: String,
+    pub width: f64,
+    pub length: Option<f64>,
+    pub num_cuts: Option<u32>,
+    // HORIZONTAL|VERTICAL == 0|1
+    pub orient: Option<u8>,
+}
+
+pub struct Lef58EolKeepout {
+    pub eol_width: f64,
+    pub extension: (f64, f64, f64), // (backward, side, forward)
+    pub corner_only: bool,
+    pub except_within: Option<(f64, f64)>,
+}
+
+pub struct Lef58CornerSpacing {
+    // CONVEXCORNER|CONCAVECORNER == 0|1
+    pub corner_type: u8,
+    pub same_mask: bool,
+    pub corner_only: Option<f64>,
+    pub except_eol: Option<f64>,
+    pub spacing: Vec<(f64, f64, f64)>, // (width, horizontal, vertical)
+}
+
+pub struct Lef58EdgeType {
+    // RIGHT|LEFT|TOP|BOTTOM == 0|1|2|3
+    pub edge: u8,
+    pub edge_type: String,
+    pub cell_row: Option<u32>,
+    pub half_row: bool,
+    pub range: Option<(f64, f64)>,
+}
+
+#[derive(PartialEq)]
+pub struct LefUnits {
+    pub time: Option<f64>,        // NANOSECONDS
+    pub capacitance: Option<f64>, // PICOFARADS
+    pub resistance: Option<f64>,  // OHMS
+    pub power: Option<f64>,       // MILLIWATTS
+    pub current: Option<f64>,     // MILLIAMPS
+    pub voltage: Option<f64>,     // VOLTS
+    pub database: Option<u32>,    // DATABASE MICRONS
+    pub frequency: Option<f64>,   // MEGAHERTZ
+}
+
+impl LefData {
+    /// database units per micron, from UNITS DATABASE MICRONS. Defaults to 100.
+    pub fn dbu_per_micron(&self) -> u32 {
+        self.units.as_ref().and_then(|x| x.database).unwrap_or(100)
+    }
+
+    pub fn to_dbu(&self, micron: f64) -> i32 {
+        (micron * self.dbu_per_micron() as f64).round() as i32
+    }
+
+    pub fn to_micron(&self, dbu: i32) -> f64 {
+        dbu as f64 / self.dbu_per_micron() as f64
+    }
+
+    /// rounds `micron` to the nearest point of the MANUFACTURINGGRID, independent of
+    /// the database units. The value is returned unchanged if the library has no grid.
+    pub fn snap_to_grid(&self, micron: f64) -> f64 {
+        match self.manufacturing_grid {
+            Some(grid) if grid > 0.0 => (micron / grid).round() * grid,
+            _ => micron,
+        }
+    }
+}
+
+// the layer table keeps the declaration order of the LAYER statements, which is
+// the physical stack from bottom to top. A layer's index is its position in `layer`.
+impl LefData {
+    pub fn find_layer(&self, layer_name: &str) -> Option<&LefLayer> {
+        self.layer.iter().find(|x| x.layer_name == layer_name)
+    }
+
+    pub fn layer_index(&self, layer_name: &str) -> Option<usize> {
+        self.layer.iter().position(|x| x.layer_name == layer_name)
+    }
+
+    /// nearest routing layer above `layer_name`, ie. metal2 for metal1.
+    pub fn routing_layer_above(&self, layer_name: &str) -> Option<&LefLayer> {
+        self.adjacent_layer(layer_name, 0, true)
+    }
+
+    /// nearest routing layer below `layer_name`.
+    pub fn routing_layer_below(&self, layer_name: &str) -> Option<&LefLayer> {
+        self.adjacent_layer(layer_name, 0, false)
+    }
+
+    /// nearest cut layer above `layer_name`.
+    pub fn cut_layer_above(&self, layer_name: &str) -> Option<&LefLayer> {
+        self.adjacent_layer(layer_name, 1, true)
+    }
+
+    /// nearest cut layer below `layer_name`.
+    pub fn cut_layer_below(&self, layer_name: &str) -> Option<&LefLayer> {
+        self.adjacent_layer(layer_name, 1, false)
+    }
+
+    /// cut layer connecting two neighbouring routing layers, in either order.
+    /// None if the two layers are not adjacent in the routing stack.
+    pub fn cut_between(&self, layer1: &str, layer2: &str) -> Option<&LefLayer> {
+        let idx1 = self.layer_index(layer1)?;
+        let idx2 = self.layer_index(layer2)?;
+        let (lo, hi) = if idx1 < idx2 {
+            (idx1, idx2)
+        } else {
+            (idx2, idx1)
+        };
+        let between = &self.layer[lo + 1..hi];
+        if between.iter().any(|x| x.layer_type == 0) {
+            return None;
+        }
+        between.iter().find(|x| x.layer_type == 1)
+    }
+
+    fn adjacent_layer(&self, layer_name: &str, layer_type: u8, above: bool) -> Option<&LefLayer> {
+        let idx = self.layer_index(layer_name)?;
+        if above {
+            self.layer[idx + 1..]
+                .iter()
+                .find(|x| x.layer_type == layer_type)
+        } else {
+            self.layer[..idx]
+                .iter()
+                .rev()
+                .find(|x| x.layer_type == layer_type)
+        }
+    }
+}
+
+pub struct LefLayer {
+    pub layer_name: String,
+    // ROUTING|CUT|MASTERSLICE|OVERLAP|IMPLANT == 0|1|2|3|4
+    pub layer_type: u8,
+    // HORIZONTAL|VERTICAL|DIAG45|DIAG135 == 0|1|2|3
+    pub direction: Option<u8>,
+    pub pitch: Option<(f64, f64)>, // (x, y). a single distance applies to both
+    pub offset: Option<(f64, f64)>, // (x, y). a single distance applies to both
+    pub width: Option<f64>,
+    pub min_width: Option<f64>,
+    pub max_width: Option<f64>,
+    pub area: Option<f64>,
+    pub spacing: Vec<LayerSpacing>,
+    pub prl_spacing_table: Option<PrlSpacingTable>,
+    pub two_widths_spacing_table: Option<TwoWidthsSpacingTable>,
+    pub influence_spacing_table: Vec<(f64, f64, f64)>, // (width, within, spacing)
+    pub enclosure: Vec<LayerEnclosure>,                // cut layer only
+    pub thickness: Option<f64>,
+    pub height: Option<f64>,
+    pub resistance: Option<f64>, // RESISTANCE RPERSQ, or resistance per cut for cut layers
+    pub capacitance: Option<f64>, // CAPACITANCE CPERSQDIST
+    pub edge_capacitance: Option<f64>, // EDGECAPACITANCE
+    pub min_step: Vec<MinStep>,
+    pub minimum_cut: Vec<MinimumCut>,
+    pub min_enclosed_area: Vec<(f64, Option<f64>)>, // (area, width)
+    pub protrusion_width: Option<(f64, f64, f64)>,  // (width1, length, width2)
+    pub minimum_density: Option<f64>,
+    pub maximum_density: Option<f64>,
+    pub density_check_window: Option<(f64, f64)>, // (length, width)
+    pub density_check_step: Option<f64>,
+    pub property: Vec<(String, PropValue)>,
+    pub lef58_rule: Vec<Lef58Rule>, // decoded from the LEF58_* properties
+    pub antenna: Vec<LayerAntenna>, // one entry per ANTENNAMODEL
+    pub unparsed: Vec<String>,      // statements the parser does not know, as written
+    pub source_file: Option<String>, // file the object was read from
+    pub comments: Vec<String>,      // `#` comments in front of the object
+}
+
+// antenna rules of a layer for one oxide model
+pub struct LayerAntenna {
+    // OXIDE1|OXIDE2|OXIDE3|OXIDE4 == 0|1|2|3
+    pub model: u8,
+    pub area_ratio: Option<f64>,
+    pub diff_area_ratio: Option<AntennaRatio>,
+    pub cum_area_ratio: Option<f64>,
+    pub cum_diff_area_ratio: Option<AntennaRatio>,
+    pub area_factor: Option<(f64, bool)>, // (factor, if DIFFUSEONLY)
+    pub side_area_ratio: Option<f64>,
+    pub diff_side_area_ratio: Option<AntennaRatio>,
+    pub cum_side_area_ratio: Option<f64>,
+    pub cum_diff_side_area_ratio: Option<AntennaRatio>,
+    pub side_area_factor: Option<(f64, bool)>, // (factor, if DIFFUSEONLY)
+    pub cum_routing_plus_cut: bool,
+    pub gate_plus_diff: Option<f64>,
+    pub area_minus_diff: Option<f64>,
+    pub area_diff_reduce_pwl: Option<Vec<(f64, f64)>>, // (diff_area, metal_diff_factor)
+}
+
+pub enum AntennaRatio {
+    Value(f64),
+    Pwl(Vec<(f64, f64)>), // (diff_area, ratio)
+}
+
+impl AntennaRatio {
+    /// ratio for a diffusion area. PWL tables are interpolated linearly between
+    /// their points and held constant outside of them.
+    pub fn eval(&self, diff_area: f64) -> f64 {
+        match self {
+            AntennaRatio::Value(x) => *x,
+            AntennaRatio::Pwl(points) => match points.iter().position(|x| x.0 > diff_area) {
+                None => points.last().map_or(0.0, |x| x.1),
+                Some(0) => points[0].1,
+                Some(i) => {
+                    let (d1, r1) = points[i - 1];
+                    let (d2, r2) = points[i];
+                    r1 + (r2 - r1) * (diff_area - d1) / (d2 - d1)
+                }
+            },
+        }
+    }
+}
+
+impl LefLayer {
+    pub fn antenna_model(&self, model: u8) -> Option<&LayerAntenna> {
+        self.antenna.iter().find(|x| x.model == model)
+    }
+}
+
+impl LayerAntenna {
+    /// ANTENNADIFFAREARATIO limit for a gate connected to `diff_area` of diffusion.
+    pub fn diff_area_ratio_at(&self, diff_area: f64) -> Option<f64> {
+        self.diff_area_ratio.as_ref().map(|x| x.eval(diff_area))
+    }
+
+    /// ANTENNACUMDIFFAREARATIO limit for a gate connected to `diff_area` of diffusion.
+    pub fn cum_diff_area_ratio_at(&self, diff_area: f64) -> Option<f64> {
+        self.cum_diff_area_ratio.as_ref().map(|x| x.eval(diff_area))
+    }
+}
+
+pub struct LayerSpacing {
+    pub min_spacing: f64,
+    pub range: Option<(f64, f64)>, // (min_width, max_width)
+    pub length_threshold: Option<f64>,
+    pub end_of_line: Option<EndOfLineSpacing>,
+    // SAMENET rule, the value tells if PGONLY
+    pub same_net: Option<bool>,
+    pub center_to_center: bool, // cut layer only
+    pub cut_rule: Option<CutSpacingRule>,
+}
+
+// cut layer spacing that only applies in some cases
+pub enum CutSpacingRule {
+    Layer((String, bool)),          // (second layer name, if STACK)
+    AdjacentCuts((u32, f64, bool)), // (cuts, within, if EXCEPTSAMEPGNET)
+    ParallelOverlap,
+    Area(f64),
+}
+
+pub struct EndOfLineSpacing {
+    pub eol_width: f64,
+    pub eol_within: f64,
+    // (par_space, par_within, if TWOEDGES)
+    pub parallel_edge: Option<(f64, f64, bool)>,
+}
+
+pub struct MinStep {
+    pub min_step_length: f64,
+    // INSIDECORNER|OUTSIDECORNER|STEP == 0|1|2
+    pub step_type: Option<u8>,
+    pub length_sum: Option<f64>,
+    pub max_edges: Option<u32>,
+}
+
+pub struct MinimumCut {
+    pub num_cuts: u32,
+    pub width: f64,
+    pub within: Option<f64>,
+    // FROMABOVE|FROMBELOW == 0|1. None applies to both
+    pub from: Option<u8>,
+    pub length: Option<(f64, f64)>,       // (length, within)
+    pub area: Option<(f64, Option<f64>)>, // (area, within)
+}
+
+// SPACINGTABLE PARALLELRUNLENGTH. spacing[i][j] applies to width[i] and length[j]
+pub struct PrlSpacingTable {
+    pub length: Vec<f64>,
+    pub width: Vec<f64>,
+    pub spacing: Vec<Vec<f64>>,
+}
+
+// SPACINGTABLE TWOWIDTHS. spacing[i][j] applies between width[i] and width[j]
+pub struct TwoWidthsSpacingTable {
+    pub width: Vec<f64>,
+    pub prl: Vec<Option<f64>>,
+    pub spacing: Vec<Vec<f64>>,
+}
+
+impl LefLayer {
+    /// minimum spacing between two shapes of `width1` and `width2` facing each other
+    /// over `parallel_run_length`. Tables are looked up as the LEF spec describes: a
+    /// row or column applies once the actual value is greater than its index, the
+    /// first one always applies. PARALLELRUNLENGTH rows are indexed by the wider shape.
+    /// Plain SPACING statements that match one of the widths are taken into account too.
+    /// SPACINGTABLE INFLUENCE is not applied here since it depends on the distance to a
+    /// wide wire, see `influence_spacing`.
+    pub fn required_spacing(
+        &self,
+        width1: f64,
+        width2: f64,
+        parallel_run_length: f64,
+    ) -> Option<f64> {
+        let mut spacing: Option<f64> = None;
+        let mut require = |x: f64| spacing = Some(spacing.map_or(x, |s| s.max(x)));
+
+        // end-of-line, same-net and cut rules do not depend on the widths.
+        for rule in self.spacing.iter().filter(|x| {
+            x.end_of_line.is_none()
+                && x.same_net.is_none()
+                && x.cut_rule.is_none()
+                && x.length_threshold.is_none_or(|l| parallel_run_length <= l)
+        }) {
+            match rule.range {
+                None => require(rule.min_spacing),
+                Some((min, max)) => {
+                    if (min..=max).contains(&width1) || (min..=max).contains(&width2) {
+                        require(rule.min_spacing)
+                    }
+                }
+            }
+        }
+        if let Some(table) = &self.prl_spacing_table {
+            let row = table_index(&table.width, width1.max(width2));
+            let col = table_index(&table.length, parallel_run_length);
+            if let Some(x) = table.spacing.get(row).and_then(|r| r.get(col)) {
+                require(*x);
+            }
+        }
+        if let Some(table) = &self.two_widths_spacing_table {
+            let row = table.width_index(width1, parallel_run_length);
+            let col = table.width_index(width2, parallel_run_length);
+            if let Some(x) = table.spacing.get(row).and_then(|r| r.get(col)) {
+                require(*x);
+            }
+        }
+        spacing
+    }
+
+    /// minimum spacing from SPACINGTABLE INFLUENCE for shapes `distance` away from a
+    /// wire of `wide_width`. Every row whose WIDTH the wire reaches and whose WITHIN
+    /// covers the distance applies, the largest SPACING wins.
+    pub fn influence_spacing(&self, wide_width: f64, distance: f64) -> Option<f64> {
+        self.influence_spacing_table
+            .iter()
+            .filter(|(width, within, _)| wide_width >= *width && distance <= *within)
+            .map(|x| x.2)
+            .reduce(f64::max)
+    }
+}
+
+impl TwoWidthsSpacingTable {
+    // a row with PRL only applies when the shapes run in parallel for longer than it.
+    fn width_index(&self, width: f64, parallel_run_length: f64) -> usize {
+        self.width
+            .iter()
+            .zip(self.prl.iter())
+            .enumerate()
+            .skip(1)
+            .rev()
+            .find(|(_, (w, prl))| width > **w && prl.is_none_or(|x| parallel_run_length > x))
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+// index of the last entry the value is greater than. The first entry always applies.
+fn table_index(index: &[f64], value: f64) -> usize {
+    index.iter().skip(1).take_while(|x| value > **x).count()
+}
+
+pub struct LayerEnclosure {
+    // ABOVE|BELOW == 0|1. None applies to both routing layers
+    pub position: Option<u8>,
+    pub overhang: (f64, f64),
+    pub min_width: Option<f64>,
+}
+
+pub struct LefVia {
+    pub via_name: String,
+    pub is_default: bool,
+    pub resistance: Option<f64>,
+    pub via_layer: Vec<PortShape>, // shapes on each layer, relative to the via origin
+    pub via_rule: Option<ViaRuleParams>, // parameters of a via generated by a VIARULE
+    pub property: Vec<(String, PropValue)>,
+    pub source_file: Option<String>, // file the object was read from
+    pub comments: Vec<String>,       // `#` comments in front of the object
+}
+
+// VIA viaName VIARULE viaRuleName ; CUTSIZE ... END viaName
+pub struct ViaRuleParams {
+    pub rule_name: String,
+    pub cut_size: (f64, f64),
+    pub layers: (String, String, String), // (bottom metal, cut, top metal)
+    pub cut_spacing: (f64, f64),
+    pub enclosure: ((f64, f64), (f64, f64)), // (bottom (x, y), top (x, y))
+    pub row_col: (u32, u32),                 // (rows, columns)
+    pub origin: (f64, f64),
+    pub offset: ((f64, f64), (f64, f64)), // (bottom (x, y), top (x, y))
+    pub pattern: Option<String>,
+}
+
+impl ViaRuleParams {
+    /// shapes of the bottom metal, cut and top metal layer. The cut array is centered
+    /// at ORIGIN, OFFSET moves the metal shapes only. PATTERN is not applied.
+    pub fn via_layer(&self) -> Vec<PortShape> {
+        let (rows, cols) = self.row_col;
+        let pitch = (
+            self.cut_size.0 + self.cut_spacing.0,
+            self.cut_size.1 + self.cut_spacing.1,
+        );
+        let half = (
+            (cols as f64 * pitch.0 - self.cut_spacing.0) / 2.0,
+            (rows as f64 * pitch.1 - self.cut_spacing.1) / 2.0,
+        );
+        let origin = self.origin;
+        let metal = |enclosure: (f64, f64), offset: (f64, f64)| {
+            let center = (origin.0 + offset.0, origin.1 + offset.1);
+            vec![PortGeometry::Rect((
+                (
+                    (
+                        center.0 - half.0 - enclosure.0,
+                        center.1 - half.1 - enclosure.1,
+                    ),
+                    (
+                        center.0 + half.0 + enclosure.0,
+                        center.1 + half.1 + enclosure.1,
+                    ),
+                ),
+                None,
+            ))]
+        };
+        let cuts = (0..rows)
+            .flat_map(|r| (0..cols).map(move |c| (c, r)))
+            .map(|(c, r)| {
+                let x = origin.0 - half.0 + c as f64 * pitch.0;
+                let y = origin.1 - half.1 + r as f64 * pitch.1;
+                PortGeometry::Rect((((x, y), (x + self.cut_size.0, y + self.cut_size.1)), None))
+            })
+            .collect();
+        let shape = |layer_name: &String, geometries| PortShape {
+            layer_name: layer_name.clone(),
+            except_pgnet: false,
+            min_spacing: None,
+            mask: None,
+            geometries,
+        };
+        vec![
+            shape(&self.layers.0, metal(self.enclosure.0, self.offset.0)),
+            shape(&self.layers.1, cuts),
+            shape(&self.layers.2, metal(self.enclosure.1, self.offset.1)),
+        ]
+    }
+}
+
+impl LefData {
+    /// library via, or one defined in a NONDEFAULTRULE.
+    pub fn find_via(&self, via_name: &str) -> Option<&LefVia> {
+        self.via
+            .iter()
+            .chain(self.nondefaultrule.iter().flat_map(|x| &x.via))
+            .find(|x| x.via_name == via_name)
+    }
+
+    /// per-layer shapes of a `PortGeometry::Via`, placed at its location.
+    /// None for other geometries or if the via is not defined in the library.
+    pub fn resolve_via(&self, geometry: &PortGeometry) -> Option<Vec<PortShape>> {
+        match geometry {
+            PortGeometry::Via((via_name, location, _)) => self.find_via(via_name).map(|via| {
+                via.via_layer
+                    .iter()
+                    .map(|x| PortShape {
+                        layer_name: x.layer_name.clone(),
+                        except_pgnet: x.except_pgnet,
+                        min_spacing: x.min_spacing,
+                        mask: x.mask,
+                        geometries: x.geometries.iter().map(|g| g.offset(*location)).collect(),
+                    })
+                    .collect()
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub struct LefViaRule {
+    pub rule_name: String,
+    pub is_generate: bool,
+    pub is_default: bool,
+    pub rule_layer: Vec<ViaRuleLayer>,
+    pub via_name: Vec<String>, // fixed vias listed by a non GENERATE rule
+    pub property: Vec<(String, PropValue)>,
+    pub source_file: Option<String>, // file the object was read from
+    pub comments: Vec<String>,       // `#` comments in front of the object
+}
+
+pub struct ViaRuleLayer {
+    pub layer_name: String,
+    // HORIZONTAL|VERTICAL == 0|1
+    pub direction: Option<u8>,
+    pub enclosure: Option<(f64, f64)>, // (overhang1, overhang2)
+    pub width: Option<(f64, f64)>,     // (min_width, max_width)
+    // cut layer only
+    pub cut_rect: Option<Rect>,
+    pub cut_spacing: Option<(f64, f64)>, // center to center (x, y)
+    pub resistance: Option<f64>,
+}
+
+// via array built from a VIARULE GENERATE. shapes are in absolute coordinates
+pub struct GeneratedVia {
+    pub rule_name: String,
+    pub cut_array: (u32, u32), // (columns, rows)
+    pub via_layer: Vec<PortShape>,
+}
+
+impl LefData {
+    pub fn find_viarule(&self, rule_name: &str) -> Option<&LefViaRule> {
+        self.viarule.iter().find(|x| x.rule_name == rule_name)
+    }
+
+    /// builds the largest cut array that fits into `overlap`, the intersection of two
+    /// wires on the routing layers `layer1` and `layer2`. DEFAULT rules are preferred,
+    /// and a rule is only used if the wire of each layer fits that layer's WIDTH range.
+    /// A wire's width is the overlap size across its DIRECTION, taken from the rule or
+    /// else from the LAYER. Without any direction the smaller side is used.
+    /// An empty overlap, or one that needs more cuts than a `u32` counts, is an error.
+    pub fn generate_via(
+        &self,
+        overlap: Rect,
+        layer1: &str,
+        layer2: &str,
+    ) -> Result<Option<GeneratedVia>, String> {
+        let overlap_size = (overlap.1 .0 - overlap.0 .0, overlap.1 .1 - overlap.0 .1);
+        if !(overlap_size.0 > 0.0 && overlap_size.1 > 0.0) {
+            return Err(format!("via overlap {:?} is empty", overlap));
+        }
+        let wire_width = |x: &ViaRuleLayer| {
+            let direction = x
+                .direction
+                .or_else(|| self.find_layer(&x.layer_name).and_then(|l| l.direction));
+            match direction {
+                Some(0) => overlap_size.1,
+                Some(1) => overlap_size.0,
+                _ => overlap_size.0.min(overlap_size.1),
+            }
+        };
+        let mut rules: Vec<&LefViaRule> = self
+            .viarule
+            .iter()
+            .filter(|x| x.is_generate && x.connects(layer1, layer2))
+            .collect();
+        rules.sort_by_key(|x| !x.is_default);
+        for x in rules.into_iter().filter(|x| {
+            x.metal_layers().all(|l| {
+                l.width
+                    .is_none_or(|(min, max)| (min..=max).contains(&wire_width(l)))
+            })
+        }) {
+            if let Some(via) = x.generate(overlap)? {
+                return Ok(Some(via));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl LefViaRule {
+    fn metal_layers(&self) -> impl Iterator<Item = &ViaRuleLayer> {
+        self.rule_layer.iter().filter(|x| x.cut_rect.is_none())
+    }
+
+    fn connects(&self, layer1: &str, layer2: &str) -> bool {
+        let metal: Vec<&str> = self.metal_layers().map(|x| x.layer_name.as_str()).collect();
+        metal.len() == 2 && metal.contains(&layer1) && metal.contains(&layer2)
+    }
+
+    // each metal layer may apply its two overhangs either on x or on y. every
+    // combination is tried and the one giving the most cuts wins.
+    fn generate(&self, overlap: Rect) -> Result<Option<GeneratedVia>, String> {
+        let (cut, ((x1, y1), (x2, y2))) = match self
+            .rule_layer
+            .iter()
+            .find_map(|x| x.cut_rect.map(|r| (x, r)))
+        {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let cut_size = (x2 - x1, y2 - y1);
+        let pitch = cut.cut_spacing.unwrap_or(cut_size);
+        let overlap_size = (overlap.1 .0 - overlap.0 .0, overlap.1 .1 - overlap.0 .1);
+        let too_many = || {
+            format!(
+                "VIARULE {}: too many cuts for {:?}",
+                self.rule_name, overlap
+            )
+        };
+        let count = |avail: f64, size: f64, pitch: f64| -> Result<u32, String> {
+            if avail + 1e-6 < size {
+                Ok(0)
+            } else if pitch <= 0.0 {
+                Ok(1)
+            } else {
+                let n = ((avail - size) / pitch + 1e-3).floor();
+                if n < u32::MAX as f64 {
+                    Ok(n as u32 + 1)
+                } else {
+                    Err(too_many())
+                }
+            }
+        };
+
+        let metal: Vec<&ViaRuleLayer> = self.metal_layers().collect();
+        type Candidate = (u32, (u32, u32), Vec<(f64, f64)>); // (cuts, (columns, rows), overhangs)
+        let mut best: Option<Candidate> = None;
+        for flip in 0..(1 << metal.len()) {
+            let overhang: Vec<(f64, f64)> = metal
+                .iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    let (o1, o2) = x.enclosure.unwrap_or((0.0, 0.0));
+                    if flip & (1 << i) == 0 {
+                        (o1, o2)
+                    } else {
+                        (o2, o1)
+                    }
+                })
+                .collect();
+            let ex = overhang.iter().fold(0.0f64, |m, x| m.max(x.0));
+            let ey = overhang.iter().fold(0.0f64, |m, x| m.max(x.1));
+            let cols = count(overlap_size.0 - 2.0 * ex, cut_size.0, pitch.0)?;
+            let rows = count(overlap_size.1 - 2.0 * ey, cut_size.1, pitch.1)?;
+            let cuts = cols.checked_mul(rows).ok_or_else(too_many)?;
+            if best.as_ref().is_none_or(|(n, _, _)| cuts > *n) {
+                best = Some((cuts, (cols, rows), overhang));
+            }
+        }
+        let (cuts, (cols, rows), overhang) = match best {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        if cuts == 0 {
+            return Ok(None);
+        }
+
+        let array_size = (
+            (cols - 1) as f64 * pitch.0 + cut_size.0,
+            (rows - 1) as f64 * pitch.1 + cut_size.1,
+        );
+        let ll = (
+            (overlap.0 .0 + overlap.1 .0 - array_size.0) / 2.0,
+            (overlap.0 .1 + overlap.1 .1 - array_size.1) / 2.0,
+        );
+        let mut overhang = overhang.into_iter();
+        let via_layer = self
+            .rule_layer
+            .iter()
+            .map(|x| {
+                let geometries = if x.cut_rect.is_some() {
+                    (0..rows)
+                        .flat_map(|r| (0..cols).map(move |c| (c, r)))
+                        .map(|(c, r)| {
+                            let x = ll.0 + c as f64 * pitch.0;
+                            let y = ll.1 + r as f64 * pitch.1;
+                            PortGeometry::Rect((((x, y), (x + cut_size.0, y + cut_size.1)), None))
+                        })
+                        .collect()
+                } else {
+                    let (ex, ey) = overhang.next().unwrap_or((0.0, 0.0));
+                    vec![PortGeometry::Rect((
+                        (
+                            (ll.0 - ex, ll.1 - ey),
+                            (ll.0 + array_size.0 + ex, ll.1 + array_size.1 + ey),
+                        ),
+                        None,
+                    ))]
+                };
+                PortShape {
+                    layer_name: x.layer_name.clone(),
+                    except_pgnet: false,
+                    min_spacing: None,
+                    mask: None,
+                    geometries,
+                }
+            })
+            .collect();
+        Ok(Some(GeneratedVia {
+            rule_name: self.rule_name.clone(),
+            cut_array: (cols, rows),
+            via_layer,
+        }))
+    }
+}
+
+pub struct NonDefaultRule {
+    pub rule_name: String,
+    pub hard_spacing: bool,
+    pub rule_layer: Vec<NonDefaultRuleLayer>,
+    pub via: Vec<LefVia>,
+    pub use_via: Vec<String>,
+    pub use_viarule: Vec<String>,
+    pub min_cuts: Vec<(String, u32)>, // (cut_layer_name, num_cuts)
+    pub property: Vec<(String, PropValue)>,
+    pub source_file: Option<String>, // file the object was read from
+    pub comments: Vec<String>,       // `#` comments in front of the object
+}
+
+pub struct NonDefaultRuleLayer {
+    pub layer_name: String,
+    pub width: f64,
+    pub diag_width: Option<f64>,
+    pub spacing: Option<f64>,
+    pub wire_extension: Option<f64>,
+    pub resistance: Option<f64>,       // RESISTANCE RPERSQ
+    pub capacitance: Option<f64>,      // CAPACITANCE CPERSQDIST
+    pub edge_capacitance: Option<f64>, // EDGECAPACITANCE
+}
+
+impl LefData {
+    pub fn find_nondefaultrule(&self, rule_name: &str) -> Option<&NonDefaultRule> {
+        self.nondefaultrule
+            .iter()
+            .find(|x| x.rule_name == rule_name)
+    }
+
+    /// NONDEFAULTRULE named by the TAPERRULE of `pin`. None if the pin has no
+    /// TAPERRULE, uses the DEFAULT rule or names an unknown rule.
+    pub fn taper_rule(&self, pin: &MacroPin) -> Option<&NonDefaultRule> {
+        match pin.taper_rule.as_deref() {
+            None | Some("DEFAULT") => None,
+            Some(x) => self.find_nondefaultrule(x),
+        }
+    }
 }
 
 pub struct LefSite {
@@ -14,83 +1118,301 @@ pub struct LefSite {
     pub site_symmetry: Option<Vec<u8>>,
     // row_pattern: previous_row_name, orient_code
     pub row_pattern: Option<Vec<(String, u8)>>,
-    pub site_size: (f32, f32), // width ,height
+    pub site_size: (f64, f64),       // width ,height
+    pub source_file: Option<String>, // file the object was read from
+    pub comments: Vec<String>,       // `#` comments in front of the object
 }
 
+impl LefData {
+    pub fn find_site(&self, site_name: &str) -> Option<&LefSite> {
+        self.site.iter().find(|x| x.site_name == site_name)
+    }
+
+    pub fn find_macro(&self, macro_name: &str) -> Option<&LefMacro> {
+        self.macro_.iter().find(|x| x.macro_name == macro_name)
+    }
+}
+
+pub type ForeignCell = (String, Option<(f64, f64)>, Option<u8>); // (cell, origin, orient)
+
 pub struct LefMacro {
     pub macro_name: String,
-    pub macro_class: u8,
-    pub foreign_cell: (String, Option<(f32, f32)>, Option<u8>),
-    pub origin: (f32, f32),
+    pub macro_class: Option<u8>,
+    pub fixed_mask: bool,
+    pub foreign_cell: Vec<ForeignCell>,
+    pub origin: (f64, f64), // 0 0 if not given
     pub eeq_macro: Option<String>,
-    pub macro_size: (f32, f32),
+    pub leq_macro: Option<String>,
+    // USER|GENERATE|BLOCK == 0|1|2
+    pub source: Option<u8>,
+    pub power: Option<f64>,
+    pub macro_size: Option<(f64, f64)>,
     pub macro_symmetry: Vec<u8>,
     pub macro_site: Vec<MacroSite>,
     pub macro_pin: Vec<MacroPin>,
-    pub macro_obs: Option<Vec<PortShape>>,
-    pub macro_density: Option<MacroDensity>,
+    pub macro_obs: Vec<MacroObs>,
+    pub macro_density: Vec<MacroDensity>, // one per DENSITY LAYER
+    pub property: Vec<(String, PropValue)>,
+    pub lef58_rule: Vec<Lef58Rule>, // decoded from the LEF58_* properties
+    pub source_file: Option<String>, // file the object was read from
+    pub comments: Vec<String>,      // `#` comments in front of the object
 }
 
+// pins of a macro sharing one bus name
+pub struct PinBus {
+    pub bus_name: String,
+    pub msb: i32,               // highest bit
+    pub lsb: i32,               // lowest bit
+    pub bit: Vec<(i32, usize)>, // (bit, index in macro_pin), in pin order
+}
+
+impl LefData {
+    /// splits a bus bit name by BUSBITCHARS, ie. `D[3]` into (`D`, 3). None for a
+    /// scalar name or if the bus characters are escaped.
+    pub fn split_bus_bit<'a>(&self, name: &'a str) -> Option<(&'a str, i32)> {
+        let mut chars = self.busbitchar.chars();
+        let (open, close) = (chars.next()?, chars.next()?);
+        let body = name.strip_suffix(close)?;
+        let idx = body.rfind(open)?;
+        if idx == 0 || is_escaped(name, idx) || is_escaped(name, body.len()) {
+            return None;
+        }
+        body[idx + open.len_utf8()..]
+            .parse()
+            .ok()
+            .map(|bit| (&name[..idx], bit))
+    }
+
+    /// splits a hierarchical name on DIVIDERCHAR, skipping escaped dividers.
+    pub fn split_hierarchy<'a>(&self, name: &'a str) -> Vec<&'a str> {
+        let divider = match self.dividechar.chars().next() {
+            Some(x) => x,
+            None => return vec![name],
+        };
+        let mut parts = Vec::new();
+        let mut start = 0;
+        for (idx, c) in name.char_indices() {
+            if c == divider && !is_escaped(name, idx) {
+                parts.push(&name[start..idx]);
+                start = idx + c.len_utf8();
+            }
+        }
+        parts.push(&name[start..]);
+        parts
+    }
+
+    /// groups the bus bit pins of `macro_` by bus name. Buses come in the order their
+    /// first pin appears in the macro, bits keep the pin order. Scalar pins are left out.
+    pub fn pin_buses(&self, macro_: &LefMacro) -> Vec<PinBus> {
+        let mut buses: Vec<PinBus> = Vec::new();
+        for (idx, pin) in macro_.macro_pin.iter().enumerate() {
+            let (name, bit) = match self.split_bus_bit(&pin.pin_name) {
+                Some(x) => x,
+                None => continue,
+            };
+            match buses.iter_mut().find(|x| x.bus_name == name) {
+                Some(bus) => {
+                    bus.msb = bus.msb.max(bit);
+                    bus.lsb = bus.lsb.min(bit);
+                    bus.bit.push((bit, idx));
+                }
+                None => buses.push(PinBus {
+                    bus_name: name.to_string(),
+                    msb: bit,
+                    lsb: bit,
+                    bit: vec![(bit, idx)],
+                }),
+            }
+        }
+        buses
+    }
+}
+
+// if the character at byte `idx` follows an odd number of `\`
+fn is_escaped(name: &str, idx: usize) -> bool {
+    name[..idx].chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+// SITE siteName [xOrig yOrig siteOrient [DO xCount BY yCount STEP xStep yStep]] ;
 pub struct MacroSite {
     pub site_name: String,
-    pub site_pattern: Option<String>,
+    pub site_pattern: Option<((f64, f64), u8, Option<StepPattern>)>, // (origin, orient, step)
 }
 
 pub struct MacroPin {
     pub pin_name: String,
     pub taper_rule: Option<String>,
+    // INPUT|OUTPUT TRISTATE|OUTPUT|INOUT|FEEDTHRU == 0|1|2|3|4, INPUT if not given
     pub direction: u8,
     pub use_type: u8,
-    pub net_expr: Option<String>,
+    pub net_expr: Option<String>, // NETEXPR as written, with the quotes
     pub ground_sensitivity: Option<String>,
     pub supply_sensitivity: Option<String>,
     pub mustjoin: Option<String>,
     pub shape: Option<u8>,
-    pub pin_port: Vec<PortShape>, // (class,MacroPortObj) // assume only one port in each pin
-                                  // pub pin_antenna: Option<MacroPinAntenna>,
+    pub property: Vec<(String, PropValue)>,
+    pub pin_port: Vec<MacroPort>,
+    pub pin_antenna: Option<MacroPinAntenna>,
+    pub comments: Vec<String>, // `#` comments in front of the pin
+}
+
+// shapes of one PORT are strongly connected. Separate PORTs of a pin are only
+// connected through the pin itself
+pub struct MacroPort {
+    // NONE|CORE|BUMP == 0|1|2
+    pub port_class: Option<u8>,
+    pub port_shape: Vec<PortShape>,
+    pub port_via: Vec<PortGeometry>, // PortGeometry::Via, possibly under ITERATE
 }
 
+// every value is (area, LAYER qualifier). A value without LAYER applies to the pin as a whole
 pub struct MacroPinAntenna {
-    pub partial_metal_area: Option<f32>,
-    pub partial_metal_sidearea: Option<f32>,
-    pub partial_cutarea: Option<f32>,
-    pub diffarea: Option<f32>,
+    pub partial_metal_area: Vec<(f64, Option<String>)>,
+    pub partial_metal_sidearea: Vec<(f64, Option<String>)>,
+    pub partial_cutarea: Vec<(f64, Option<String>)>,
+    pub diffarea: Vec<(f64, Option<String>)>,
+    pub model: Vec<PinAntennaModel>, // one entry per ANTENNAMODEL
+    // ANTENNASIZE, ANTENNAMETALAREA and ANTENNAMETALLENGTH, obsolete since 5.4
+    pub size: Vec<(f64, Option<String>)>,
+    pub metal_area: Vec<(f64, Option<String>)>,
+    pub metal_length: Vec<(f64, Option<String>)>,
+}
+
+pub struct PinAntennaModel {
+    // OXIDE1|OXIDE2|OXIDE3|OXIDE4 == 0|1|2|3
     pub model: u8,
-    pub gatearea: Option<f32>,
-    pub max_area_car: Option<f32>,
-    pub max_sidearea_car: Option<f32>,
-    pub max_cut_car: Option<f32>,
+    pub gatearea: Vec<(f64, Option<String>)>,
+    pub max_area_car: Vec<(f64, Option<String>)>,
+    pub max_sidearea_car: Vec<(f64, Option<String>)>,
+    pub max_cut_car: Vec<(f64, Option<String>)>,
+}
+
+impl MacroPinAntenna {
+    pub fn antenna_model(&self, model: u8) -> Option<&PinAntennaModel> {
+        self.model.iter().find(|x| x.model == model)
+    }
+
+    /// total ANTENNADIFFAREA of the pin, or the one given for `layer_name` if any.
+    pub fn diff_area(&self, layer_name: Option<&str>) -> f64 {
+        antenna_area(&self.diffarea, layer_name)
+    }
+}
+
+impl PinAntennaModel {
+    /// total ANTENNAGATEAREA of the pin, or the one given for `layer_name` if any.
+    pub fn gate_area(&self, layer_name: Option<&str>) -> f64 {
+        antenna_area(&self.gatearea, layer_name)
+    }
+}
+
+fn antenna_area(values: &[(f64, Option<String>)], layer_name: Option<&str>) -> f64 {
+    match layer_name {
+        Some(name) => values
+            .iter()
+            .filter(|x| x.1.as_deref() == Some(name))
+            .map(|x| x.0)
+            .sum(),
+        None => values.iter().map(|x| x.0).sum(),
+    }
 }
 pub struct PortShape {
     pub layer_name: String, // layer name
+    pub except_pgnet: bool,
+    pub min_spacing: Option<(bool, f64)>, // (if from SPACING or DESIGNRULEWIDTH, value)
+    pub mask: Option<u32>,
     pub geometries: Vec<PortGeometry>,
 }
 
-// pub struct PortLayerGeometry {
-//     pub layer_name: String,
-//     pub if_exceptpgnet: bool,
-//     pub minspacing: Option<(bool, u32)>, //(if from spacing or designrulewidth, minspacing)
-//     pub geometries: Vec<PortGeometry>,
-// }
-
-// pub struct PortViaGeometry {
-//     pub via_name: String,
-//     pub via_location: (f32, f32),
-// }
+impl PortShape {
+    /// every geometry of the layer with ITERATE arrays expanded.
+    pub fn expanded_geometries(&self) -> impl Iterator<Item = PortGeometry> + '_ {
+        self.geometries.iter().flat_map(|x| x.expand())
+    }
+}
 
+// the last Option<u32> of every shape is its MASK. The mask of a via holds one hex digit
+// per layer, top/cut/bottom, ie. MASK 021 == 0x021
 pub enum PortGeometry {
-    Path(Vec<(f32, f32)>),
-    Rect(((f32, f32), (f32, f32))),
-    Polygon(Vec<(f32, f32)>),
-    Via((String, (f32, f32))),
+    Path((Vec<(f64, f64)>, Option<f64>, Option<u32>)), // (points, WIDTH, mask)
+    Rect((Rect, Option<u32>)),
+    Polygon((Vec<(f64, f64)>, Option<u32>)),
+    Via((String, (f64, f64), Option<u32>)),
+    Iterate((Box<PortGeometry>, StepPattern)), // geometry repeated by ITERATE
 }
 
-// pub enum MacroOBS {
-//     LayerObj(PortLayerGeometry),
-//     ViaObj(PortViaGeometry),
-// }
+// DO numX BY numY STEP spaceX spaceY
+pub struct StepPattern {
+    pub num_x: u32,
+    pub num_y: u32,
+    pub space_x: f64,
+    pub space_y: f64,
+}
+
+impl PortGeometry {
+    /// MASK number of the geometry, the one of the repeated geometry for ITERATE.
+    pub fn mask(&self) -> Option<u32> {
+        match self {
+            PortGeometry::Path((_, _, mask))
+            | PortGeometry::Rect((_, mask))
+            | PortGeometry::Polygon((_, mask))
+            | PortGeometry::Via((_, _, mask)) => *mask,
+            PortGeometry::Iterate((x, _)) => x.mask(),
+        }
+    }
+
+    /// copy of the geometry moved by (dx, dy).
+    pub fn offset(&self, delta: (f64, f64)) -> PortGeometry {
+        let move_pt = |pt: &(f64, f64)| (pt.0 + delta.0, pt.1 + delta.1);
+        match self {
+            PortGeometry::Path((x, width, mask)) => {
+                PortGeometry::Path((x.iter().map(move_pt).collect(), *width, *mask))
+            }
+            PortGeometry::Rect(((p1, p2), mask)) => {
+                PortGeometry::Rect(((move_pt(p1), move_pt(p2)), *mask))
+            }
+            PortGeometry::Polygon((x, mask)) => {
+                PortGeometry::Polygon((x.iter().map(move_pt).collect(), *mask))
+            }
+            PortGeometry::Via((name, pt, mask)) => {
+                PortGeometry::Via((name.clone(), move_pt(pt), *mask))
+            }
+            PortGeometry::Iterate((x, step)) => PortGeometry::Iterate((
+                Box::new(x.offset(delta)),
+                StepPattern {
+                    num_x: step.num_x,
+                    num_y: step.num_y,
+                    space_x: step.space_x,
+                    space_y: step.space_y,
+                },
+            )),
+        }
+    }
+
+    /// individual geometries of an ITERATE array, row by row from the first one.
+    /// Any other geometry yields a copy of itself.
+    pub fn expand(&self) -> impl Iterator<Item = PortGeometry> + '_ {
+        let (geometry, num_x, num_y, space_x, space_y) = match self {
+            PortGeometry::Iterate((x, step)) => (
+                x.as_ref(),
+                step.num_x,
+                step.num_y,
+                step.space_x,
+                step.space_y,
+            ),
+            x => (x, 1, 1, 0.0, 0.0),
+        };
+        (0..num_y).flat_map(move |j| {
+            (0..num_x).map(move |i| geometry.offset((i as f64 * space_x, j as f64 * space_y)))
+        })
+    }
+}
+
+pub enum MacroObs {
+    LayerObj(PortShape),
+    ViaObj(PortGeometry), // PortGeometry::Via, possibly under ITERATE
+}
 
 pub struct MacroDensity {
     pub layer_name: String,
-    pub rect_region: Vec<(((f32, f32), (f32, f32)), f32)>,
+    pub rect_region: Vec<(Rect, f64)>,
 }
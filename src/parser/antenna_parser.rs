@@ -0,0 +1,399 @@
+use crate::{
+    model::{AntennaRatio, LayerAntenna, MacroPinAntenna, PinAntennaModel},
+    LefRes,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{map, opt};
+use nom::error::context;
+use nom::multi::many1;
+use nom::sequence::{delimited, preceded, tuple};
+
+use super::base::{float, tstring, ws};
+use super::encoder::antenna_model_encode;
+
+pub enum LayerAntennaStatement {
+    Model(u8),
+    AreaRatio(f64),
+    DiffAreaRatio(AntennaRatio),
+    CumAreaRatio(f64),
+    CumDiffAreaRatio(AntennaRatio),
+    AreaFactor((f64, bool)),
+    SideAreaRatio(f64),
+    DiffSideAreaRatio(AntennaRatio),
+    CumSideAreaRatio(f64),
+    CumDiffSideAreaRatio(AntennaRatio),
+    SideAreaFactor((f64, bool)),
+    CumRoutingPlusCut,
+    GatePlusDiff(f64),
+    AreaMinusDiff(f64),
+    AreaDiffReducePwl(Vec<(f64, f64)>),
+}
+
+pub fn layer_antenna_statement(input: &str) -> LefRes<&str, LayerAntennaStatement> {
+    context(
+        "Layer Antenna Statement",
+        alt((
+            map(
+                delimited(ws(tag("ANTENNAMODEL")), antenna_model_encode, ws(tag(";"))),
+                LayerAntennaStatement::Model,
+            ),
+            map(
+                delimited(ws(tag("ANTENNAAREARATIO")), float, ws(tag(";"))),
+                LayerAntennaStatement::AreaRatio,
+            ),
+            map(
+                delimited(ws(tag("ANTENNADIFFAREARATIO")), antenna_ratio, ws(tag(";"))),
+                LayerAntennaStatement::DiffAreaRatio,
+            ),
+            map(
+                delimited(ws(tag("ANTENNACUMAREARATIO")), float, ws(tag(";"))),
+                LayerAntennaStatement::CumAreaRatio,
+            ),
+            map(
+                delimited(
+                    ws(tag("ANTENNACUMDIFFAREARATIO")),
+                    antenna_ratio,
+                    ws(tag(";")),
+                ),
+                LayerAntennaStatement::CumDiffAreaRatio,
+            ),
+            map(
+                delimited(ws(tag("ANTENNAAREAFACTOR")), antenna_factor, ws(tag(";"))),
+                LayerAntennaStatement::AreaFactor,
+            ),
+            map(
+                delimited(ws(tag("ANTENNASIDEAREARATIO")), float, ws(tag(";"))),
+                LayerAntennaStatement::SideAreaRatio,
+            ),
+            map(
+                delimited(
+                    ws(tag("ANTENNADIFFSIDEAREARATIO")),
+                    antenna_ratio,
+                    ws(tag(";")),
+                ),
+                LayerAntennaStatement::DiffSideAreaRatio,
+            ),
+            map(
+                delimited(ws(tag("ANTENNACUMSIDEAREARATIO")), float, ws(tag(";"))),
+                LayerAntennaStatement::CumSideAreaRatio,
+            ),
+            map(
+                delimited(
+                    ws(tag("ANTENNACUMDIFFSIDEAREARATIO")),
+                    antenna_ratio,
+                    ws(tag(";")),
+                ),
+                LayerAntennaStatement::CumDiffSideAreaRatio,
+            ),
+            map(
+                delimited(
+                    ws(tag("ANTENNASIDEAREAFACTOR")),
+                    antenna_factor,
+                    ws(tag(";")),
+                ),
+                LayerAntennaStatement::SideAreaFactor,
+            ),
+            map(
+                tuple((ws(tag("ANTENNACUMROUTINGPLUSCUT")), ws(tag(";")))),
+                |_| LayerAntennaStatement::CumRoutingPlusCut,
+            ),
+            map(
+                delimited(ws(tag("ANTENNAGATEPLUSDIFF")), float, ws(tag(";"))),
+                LayerAntennaStatement::GatePlusDiff,
+            ),
+            map(
+                delimited(ws(tag("ANTENNAAREAMINUSDIFF")), float, ws(tag(";"))),
+                LayerAntennaStatement::AreaMinusDiff,
+            ),
+            map(
+                delimited(ws(tag("ANTENNAAREADIFFREDUCEPWL")), pwl, ws(tag(";"))),
+                LayerAntennaStatement::AreaDiffReducePwl,
+            ),
+        )),
+    )(input)
+}
+
+// statements before the first ANTENNAMODEL belong to OXIDE1.
+pub fn layer_antenna_fold(
+    antenna: &mut Vec<LayerAntenna>,
+    model: &mut u8,
+    statement: LayerAntennaStatement,
+) {
+    if let LayerAntennaStatement::Model(x) = statement {
+        *model = x;
+        return;
+    }
+    let idx = match antenna.iter().position(|x| x.model == *model) {
+        Some(idx) => idx,
+        None => {
+            antenna.push(LayerAntenna {
+                model: *model,
+                area_ratio: None,
+                diff_area_ratio: None,
+                cum_area_ratio: None,
+                cum_diff_area_ratio: None,
+                area_factor: None,
+                side_area_ratio: None,
+                diff_side_area_ratio: None,
+                cum_side_area_ratio: None,
+                cum_diff_side_area_ratio: None,
+                side_area_factor: None,
+                cum_routing_plus_cut: false,
+                gate_plus_diff: None,
+                area_minus_diff: None,
+                area_diff_reduce_pwl: None,
+            });
+            antenna.len() - 1
+        }
+    };
+    let rule = &mut antenna[idx];
+    match statement {
+        LayerAntennaStatement::Model(_) => {}
+        LayerAntennaStatement::AreaRatio(x) => rule.area_ratio = Some(x),
+        LayerAntennaStatement::DiffAreaRatio(x) => rule.diff_area_ratio = Some(x),
+        LayerAntennaStatement::CumAreaRatio(x) => rule.cum_area_ratio = Some(x),
+        LayerAntennaStatement::CumDiffAreaRatio(x) => rule.cum_diff_area_ratio = Some(x),
+        LayerAntennaStatement::AreaFactor(x) => rule.area_factor = Some(x),
+        LayerAntennaStatement::SideAreaRatio(x) => rule.side_area_ratio = Some(x),
+        LayerAntennaStatement::DiffSideAreaRatio(x) => rule.diff_side_area_ratio = Some(x),
+        LayerAntennaStatement::CumSideAreaRatio(x) => rule.cum_side_area_ratio = Some(x),
+        LayerAntennaStatement::CumDiffSideAreaRatio(x) => rule.cum_diff_side_area_ratio = Some(x),
+        LayerAntennaStatement::SideAreaFactor(x) => rule.side_area_factor = Some(x),
+        LayerAntennaStatement::CumRoutingPlusCut => rule.cum_routing_plus_cut = true,
+        LayerAntennaStatement::GatePlusDiff(x) => rule.gate_plus_diff = Some(x),
+        LayerAntennaStatement::AreaMinusDiff(x) => rule.area_minus_diff = Some(x),
+        LayerAntennaStatement::AreaDiffReducePwl(x) => rule.area_diff_reduce_pwl = Some(x),
+    }
+}
+
+pub enum PinAntennaStatement {
+    Model(u8),
+    PartialMetalArea((f64, Option<String>)),
+    PartialMetalSideArea((f64, Option<String>)),
+    PartialCutArea((f64, Option<String>)),
+    DiffArea((f64, Option<String>)),
+    GateArea((f64, Option<String>)),
+    MaxAreaCar((f64, Option<String>)),
+    MaxSideAreaCar((f64, Option<String>)),
+    MaxCutCar((f64, Option<String>)),
+    Size((f64, Option<String>)),
+    MetalArea((f64, Option<String>)),
+    MetalLength((f64, Option<String>)),
+}
+
+pub fn pin_antenna_statement(input: &str) -> LefRes<&str, PinAntennaStatement> {
+    context(
+        "Macro Pin Antenna Statement",
+        alt((
+            map(
+                delimited(ws(tag("ANTENNAMODEL")), antenna_model_encode, ws(tag(";"))),
+                PinAntennaStatement::Model,
+            ),
+            map(
+                pin_antenna_area("ANTENNAPARTIALMETALAREA"),
+                PinAntennaStatement::PartialMetalArea,
+            ),
+            map(
+                pin_antenna_area("ANTENNAPARTIALMETALSIDEAREA"),
+                PinAntennaStatement::PartialMetalSideArea,
+            ),
+            map(
+                pin_antenna_area("ANTENNAPARTIALCUTAREA"),
+                PinAntennaStatement::PartialCutArea,
+            ),
+            map(
+                pin_antenna_area("ANTENNADIFFAREA"),
+                PinAntennaStatement::DiffArea,
+            ),
+            map(
+                pin_antenna_area("ANTENNAGATEAREA"),
+                PinAntennaStatement::GateArea,
+            ),
+            map(
+                pin_antenna_area("ANTENNAMAXAREACAR"),
+                PinAntennaStatement::MaxAreaCar,
+            ),
+            map(
+                pin_antenna_area("ANTENNAMAXSIDEAREACAR"),
+                PinAntennaStatement::MaxSideAreaCar,
+            ),
+            map(
+                pin_antenna_area("ANTENNAMAXCUTCAR"),
+                PinAntennaStatement::MaxCutCar,
+            ),
+            map(pin_antenna_area("ANTENNASIZE"), PinAntennaStatement::Size),
+            map(
+                pin_antenna_area("ANTENNAMETALAREA"),
+                PinAntennaStatement::MetalArea,
+            ),
+            map(
+                pin_antenna_area("ANTENNAMETALLENGTH"),
+                PinAntennaStatement::MetalLength,
+            ),
+        )),
+    )(input)
+}
+
+// gate area and the cumulative ratios following an ANTENNAMODEL belong to that model,
+// the ones before the first ANTENNAMODEL to OXIDE1.
+pub fn pin_antenna_fold(
+    antenna: &mut Option<MacroPinAntenna>,
+    model: &mut u8,
+    statement: PinAntennaStatement,
+) {
+    if let PinAntennaStatement::Model(x) = statement {
+        *model = x;
+        return;
+    }
+    let antenna = antenna.get_or_insert_with(|| MacroPinAntenna {
+        partial_metal_area: Vec::new(),
+        partial_metal_sidearea: Vec::new(),
+        partial_cutarea: Vec::new(),
+        diffarea: Vec::new(),
+        model: Vec::new(),
+        size: Vec::new(),
+        metal_area: Vec::new(),
+        metal_length: Vec::new(),
+    });
+    match statement {
+        PinAntennaStatement::Model(_) => {}
+        PinAntennaStatement::PartialMetalArea(x) => antenna.partial_metal_area.push(x),
+        PinAntennaStatement::PartialMetalSideArea(x) => antenna.partial_metal_sidearea.push(x),
+        PinAntennaStatement::PartialCutArea(x) => antenna.partial_cutarea.push(x),
+        PinAntennaStatement::DiffArea(x) => antenna.diffarea.push(x),
+        PinAntennaStatement::GateArea(x) => pin_antenna_model(&mut antenna.model, *model)
+            .gatearea
+            .push(x),
+        PinAntennaStatement::MaxAreaCar(x) => pin_antenna_model(&mut antenna.model, *model)
+            .max_area_car
+            .push(x),
+        PinAntennaStatement::MaxSideAreaCar(x) => pin_antenna_model(&mut antenna.model, *model)
+            .max_sidearea_car
+            .push(x),
+        PinAntennaStatement::MaxCutCar(x) => pin_antenna_model(&mut antenna.model, *model)
+            .max_cut_car
+            .push(x),
+        PinAntennaStatement::Size(x) => antenna.size.push(x),
+        PinAntennaStatement::MetalArea(x) => antenna.metal_area.push(x),
+        PinAntennaStatement::MetalLength(x) => antenna.metal_length.push(x),
+    }
+}
+
+fn pin_antenna_model(models: &mut Vec<PinAntennaModel>, model: u8) -> &mut PinAntennaModel {
+    match models.iter().position(|x| x.model == model) {
+        Some(idx) => &mut models[idx],
+        None => {
+            models.push(PinAntennaModel {
+                model,
+                gatearea: Vec::new(),
+                max_area_car: Vec::new(),
+                max_sidearea_car: Vec::new(),
+                max_cut_car: Vec::new(),
+            });
+            models.last_mut().unwrap()
+        }
+    }
+}
+
+// keyword value [LAYER layerName] ;
+fn pin_antenna_area<'a>(
+    keyword: &'static str,
+) -> impl FnMut(&'a str) -> LefRes<&'a str, (f64, Option<String>)> {
+    map(
+        delimited(
+            ws(tag(keyword)),
+            tuple((float, opt(preceded(ws(tag("LAYER")), tstring)))),
+            ws(tag(";")),
+        ),
+        |(area, layer)| (area, layer.map(|x| x.to_string())),
+    )
+}
+
+// value | PWL ( ( d1 r1 ) ( d2 r2 ) ... )
+fn antenna_ratio(input: &str) -> LefRes<&str, AntennaRatio> {
+    alt((
+        map(float, AntennaRatio::Value),
+        map(preceded(ws(tag("PWL")), pwl), AntennaRatio::Pwl),
+    ))(input)
+}
+
+fn pwl(input: &str) -> LefRes<&str, Vec<(f64, f64)>> {
+    delimited(
+        ws(tag("(")),
+        many1(delimited(ws(tag("(")), tuple((float, float)), ws(tag(")")))),
+        ws(tag(")")),
+    )(input)
+}
+
+// value [DIFFUSEONLY]
+fn antenna_factor(input: &str) -> LefRes<&str, (f64, bool)> {
+    tuple((float, map(opt(ws(tag("DIFFUSEONLY"))), |x| x.is_some())))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_layer_antenna() {
+        let mut antenna = Vec::new();
+        let mut model = 0;
+        for test_str in [
+            "ANTENNAAREARATIO 400 ;",
+            "ANTENNADIFFAREARATIO PWL ( ( 0 400 ) ( 0.159 400 ) ( 0.16 2200 ) ( 100 20000 ) ) ;",
+            "ANTENNAAREAFACTOR 1.0 DIFFUSEONLY ;",
+            "ANTENNAMODEL OXIDE2 ;",
+            "ANTENNACUMAREARATIO 5000 ;",
+            "ANTENNACUMROUTINGPLUSCUT ;",
+        ]
+        .iter()
+        {
+            let (_, statement) = layer_antenna_statement(test_str).unwrap();
+            layer_antenna_fold(&mut antenna, &mut model, statement);
+        }
+        assert_eq!(antenna.len(), 2);
+        assert_eq!(antenna[0].area_ratio, Some(400.0));
+        assert_eq!(antenna[0].area_factor, Some((1.0, true)));
+        assert_eq!(antenna[1].model, 1);
+        assert!(antenna[1].cum_routing_plus_cut);
+        let ratio = antenna[0].diff_area_ratio.as_ref().unwrap();
+        assert_eq!(ratio.eval(0.1), 400.0);
+        assert!((ratio.eval(50.08) - 11100.0).abs() < 0.1);
+        assert_eq!(ratio.eval(200.0), 20000.0);
+    }
+
+    #[test]
+    fn test_pin_antenna() {
+        let test_str = "ANTENNAPARTIALMETALAREA 0.12 LAYER metal1 ;
+    ANTENNAPARTIALMETALAREA 0.3 LAYER metal2 ;
+    ANTENNADIFFAREA 0.0352 LAYER metal1 ;
+    ANTENNAGATEAREA 0.02 ;
+    ANTENNAMODEL OXIDE2 ;
+    ANTENNAGATEAREA 0.04 LAYER metal1 ;
+    ANTENNAGATEAREA 0.05 LAYER metal2 ;
+    ANTENNAMAXAREACAR 120 LAYER metal2 ;
+    PORT";
+        let (res, statements) = many1(pin_antenna_statement)(test_str).unwrap();
+        assert_eq!(res.trim_start(), "PORT");
+        let mut antenna = None;
+        let mut model = 0;
+        for statement in statements {
+            pin_antenna_fold(&mut antenna, &mut model, statement);
+        }
+        let antenna = antenna.unwrap();
+        assert_eq!(antenna.partial_metal_area.len(), 2);
+        assert_eq!(
+            antenna.partial_metal_area[1],
+            (0.3, Some("metal2".to_string()))
+        );
+        assert_eq!(antenna.diff_area(None), 0.0352);
+        assert_eq!(antenna.model.len(), 2);
+        assert_eq!(antenna.antenna_model(0).unwrap().gate_area(None), 0.02);
+        let oxide2 = antenna.antenna_model(1).unwrap();
+        assert_eq!(oxide2.gate_area(Some("metal2")), 0.05);
+        assert_eq!(
+            oxide2.max_area_car,
+            vec![(120.0, Some("metal2".to_string()))]
+        );
+    }
+}
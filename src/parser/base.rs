@@ -1,40 +1,99 @@
 use nom::branch::alt;
 
-use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, alphanumeric1, char, digit1, multispace0, one_of};
-use nom::combinator::{map_res, opt, recognize};
+use nom::bytes::complete::is_not;
+use nom::character::complete::{
+    anychar, char, digit1, multispace0, multispace1, not_line_ending, one_of, space0,
+};
+use nom::combinator::{map, map_res, opt, recognize, value};
 
-use nom::multi::{many0, many1};
+use nom::multi::{many0, many0_count, many1};
 
 use crate::LefRes;
-use nom::sequence::{delimited, pair, terminated, tuple};
+use nom::sequence::{delimited, pair, preceded, tuple};
 use std::str;
 use std::str::FromStr;
 
 // basic parse.
 
 /// A combinator that takes a parser `inner` and produces a parser that also consumes both leading and
-/// trailing whitespace, returning the output of `inner`.
-pub fn ws<'a, F: 'a, O>(inner: F) -> impl FnMut(&'a str) -> LefRes<&'a str, O>
+/// trailing whitespace, returning the output of `inner`. `#` comments are skipped as leading
+/// whitespace, so the ones in front of a statement are still there for `lef_comments`. Only
+/// spaces and tabs are trailing whitespace, a comment on the same line is left for
+/// `trailing_comment`.
+pub fn ws<'a, F, O>(inner: F) -> impl FnMut(&'a str) -> LefRes<&'a str, O>
 where
-    F: FnMut(&'a str) -> LefRes<&'a str, O>,
+    F: 'a + FnMut(&'a str) -> LefRes<&'a str, O>,
 {
-    delimited(multispace0, inner, multispace0)
+    delimited(lef_space, inner, space0)
 }
 
-// // typical string
-// // ie. abcdef, de234, jkl_mn, ...
+// whitespace and `#` comments up to the end of line
+fn lef_space(input: &str) -> LefRes<&str, ()> {
+    value(
+        (),
+        many0_count(alt((
+            multispace1,
+            recognize(pair(char('#'), not_line_ending)),
+        ))),
+    )(input)
+}
+
+// // comment on the same line as the end of a statement, without the leading `#`
+pub fn trailing_comment(input: &str) -> LefRes<&str, Option<String>> {
+    opt(preceded(
+        pair(space0, char('#')),
+        map(not_line_ending, |x: &str| x.trim().to_string()),
+    ))(input)
+}
+
+// // comments in front of a statement, without the leading `#`
+pub fn lef_comments(input: &str) -> LefRes<&str, Vec<String>> {
+    many0(preceded(
+        multispace0,
+        preceded(
+            char('#'),
+            map(not_line_ending, |x: &str| x.trim().to_string()),
+        ),
+    ))(input)
+}
+
+// // name token. Any run of characters up to whitespace, `;`, `"` or a `#` comment. A `\`
+// // escapes the next character, which is kept as written.
+// // ie. abcdef, 2M, D[3], A<0>, bus/sub, net.1, CELL$1, \escaped\;name, \#1
 pub fn tstring(input: &str) -> LefRes<&str, &str> {
-    ws(recognize(pair(
-        alt((alpha1, tag("_"))),
-        many0(alt((alphanumeric1, tag("_")))),
-    )))(input)
+    ws(recognize(many1(alt((
+        is_not(" \t\r\n;\"#\\"),
+        recognize(pair(char('\\'), anychar)),
+    )))))(input)
+}
+
+// // parse string that is surrounded by " and ", the quotes are not returned.
+// // `\"` does not end the string and is returned as `"`, `\\` is returned as `\`.
+// // ie, "abc", "power1 VDD", ""
+pub fn qstring(input: &str) -> LefRes<&str, String> {
+    map(quoted, |x| {
+        let mut text = String::new();
+        let mut chars = x[1..x.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\\', Some(next @ '"')) | ('\\', Some(next @ '\\')) => {
+                    text.push(next);
+                    chars.next();
+                }
+                _ => text.push(c),
+            }
+        }
+        text
+    })(input)
 }
 
-// // parse string that is surrounded by " and ".
-// // ie, "abc", "def"
-pub fn qstring(input: &str) -> LefRes<&str, &str> {
-    ws(recognize(delimited(tag("\""), tstring, tag("\""))))(input)
+// // quoted string as written, with the quotes and escapes
+pub fn quoted(input: &str) -> LefRes<&str, &str> {
+    ws(recognize(delimited(
+        char('"'),
+        many0(alt((is_not("\"\\"), recognize(pair(char('\\'), anychar))))),
+        char('"'),
+    )))(input)
 }
 
 // // unsigned integer number
@@ -45,17 +104,85 @@ pub fn positive_number(input: &str) -> LefRes<&str, u32> {
 
 // parse signed floating number
 // The following is modified from the Python parser by Valentin Lorentz (ProgVal).
-pub fn float(input: &str) -> LefRes<&str, f32> {
+// ie. 42, -42., +42.42, .05, 1e-3, 2.5E+2
+pub fn float(input: &str) -> LefRes<&str, f64> {
     ws(map_res(
-        alt((
-            // Case one: 42. and 42.42
-            recognize(tuple((opt(char('-')), decimal, char('.'), opt(decimal)))),
-            recognize(tuple((opt(char('-')), decimal))), // case two: integer as float number
-        )),
-        |res: &str| f32::from_str(res),
+        recognize(tuple((
+            opt(one_of("+-")),
+            alt((
+                // Case one: 42. and 42.42, case two: integer as float number
+                recognize(tuple((decimal, opt(tuple((char('.'), opt(decimal))))))),
+                // case three: .42
+                recognize(tuple((char('.'), decimal))),
+            )),
+            opt(tuple((one_of("eE"), opt(one_of("+-")), decimal))),
+        ))),
+        |res: &str| f64::from_str(res),
     ))(input)
 }
 
 pub fn decimal(input: &str) -> LefRes<&str, &str> {
-    recognize(many1(terminated(one_of("0123456789"), many0(char('_')))))(input)
+    digit1(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_names() {
+        for name in [
+            "D[3]",
+            "A<0>",
+            "bus/sub",
+            "net.1",
+            "2M",
+            "CELL$1",
+            "FILL-4",
+            "\\esc\\;aped",
+            "\\#1",
+        ]
+        .iter()
+        {
+            assert_eq!(tstring(&format!("  {} ;", name)), Ok((";", *name)));
+        }
+        assert_eq!(tstring("INV;"), Ok((";", "INV")));
+        assert_eq!(tstring("INV# comment"), Ok(("# comment", "INV")));
+        assert!(tstring(" ;").is_err());
+        assert_eq!(
+            qstring("\"power1 VDD\" ;"),
+            Ok((";", "power1 VDD".to_string()))
+        );
+        assert_eq!(
+            qstring("\"a \\\"b\\\" c\""),
+            Ok(("", "a \"b\" c".to_string()))
+        );
+        assert_eq!(qstring("\"\""), Ok(("", String::new())));
+        assert_eq!(qstring("\"a\\\\b \\n\""), Ok(("", "a\\b \\n".to_string())));
+        assert_eq!(quoted(" \"a \\\"b\\\"\" ;"), Ok((";", "\"a \\\"b\\\"\"")));
+    }
+
+    #[test]
+    fn test_float() {
+        for (text, value) in [
+            ("42", 42.0),
+            ("-42.", -42.0),
+            ("+42.42", 42.42),
+            (".05", 0.05),
+            ("-.05", -0.05),
+            ("1e-3", 0.001),
+            ("2.5E+2", 250.0),
+        ]
+        .iter()
+        {
+            assert_eq!(float(text), Ok(("", *value)));
+        }
+        // the exponent needs digits, `E` starts the next token
+        assert_eq!(float("1 E"), Ok(("E", 1.0)));
+        assert_eq!(float("3e ;"), Ok(("e ;", 3.0)));
+        // no `_` digit separators in LEF numbers
+        assert_eq!(float("1_000 ;"), Ok(("_000 ;", 1.0)));
+        // a 3 mm coordinate keeps its last grid point
+        let (_, x) = float("3000.0015").unwrap();
+        assert_eq!((x * 2000.0).round() as i64, 6000003);
+    }
 }
@@ -1,36 +1,64 @@
-use super::base::{float, ws};
+use super::base::{float, positive_number, ws};
 use nom::bytes::complete::tag;
-use nom::bytes::complete::take_until;
 use nom::sequence::preceded;
 
 use nom::multi::many1;
 use nom::sequence::{separated_pair, tuple};
 // use super::def_types::{Geometry, NetCommonProperty, PropValue, Properties, RtPt};
 
+use nom::character::complete::hex_digit1;
 use nom::character::complete::space0;
-use nom::combinator::value;
+use nom::combinator::map_res;
 
-use crate::LefRes;
+use crate::{
+    model::{Rect, StepPattern},
+    LefRes,
+};
 
 // use super::encoder::{net_pattern_encode, source_type_encode, use_mode_encode};
 // common parser used in def_parser. These parser are very commonly used in def_parser so collect them together.
 
-pub fn pt(input: &str) -> LefRes<&str, (f32, f32)> {
+pub fn pt(input: &str) -> LefRes<&str, (f64, f64)> {
     separated_pair(float, space0, float)(input)
 }
 
-pub fn rect(input: &str) -> LefRes<&str, ((f32, f32), (f32, f32))> {
+pub fn rect(input: &str) -> LefRes<&str, Rect> {
     tuple((tuple((float, float)), tuple((float, float))))(input)
 }
 
-pub fn pt_list(input: &str) -> LefRes<&str, Vec<(f32, f32)>> {
-    many1(pt)(input)
+// DO numX BY numY STEP spaceX spaceY
+pub fn step_pattern(input: &str) -> LefRes<&str, StepPattern> {
+    tuple((
+        preceded(ws(tag("DO")), positive_number),
+        preceded(ws(tag("BY")), positive_number),
+        preceded(ws(tag("STEP")), tuple((float, float))),
+    ))(input)
+    .map(|(res, data)| {
+        (
+            res,
+            StepPattern {
+                num_x: data.0,
+                num_y: data.1,
+                space_x: (data.2).0,
+                space_y: (data.2).1,
+            },
+        )
+    })
+}
+
+// MASK maskNum
+pub fn mask(input: &str) -> LefRes<&str, u32> {
+    preceded(ws(tag("MASK")), positive_number)(input)
 }
 
-// modified from nom::recipies
-pub fn lef_comment(input: &str) -> LefRes<&str, ()> {
-    value(
-        (), // Output is thrown away.
-        preceded(ws(tag("#")), take_until("VERSION")),
+// MASK viaMaskNum, one hex digit per layer
+pub fn via_mask(input: &str) -> LefRes<&str, u32> {
+    preceded(
+        ws(tag("MASK")),
+        ws(map_res(hex_digit1, |x| u32::from_str_radix(x, 16))),
     )(input)
 }
+
+pub fn pt_list(input: &str) -> LefRes<&str, Vec<(f64, f64)>> {
+    many1(pt)(input)
+}
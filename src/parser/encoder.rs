@@ -77,6 +77,14 @@ pub fn macro_class_encode(input: &str) -> LefRes<&str, u8> {
     )))(input)
 }
 
+pub fn macro_source_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((
+        map(tag("USER"), |_| 0),
+        map(tag("GENERATE"), |_| 1),
+        map(tag("BLOCK"), |_| 2),
+    )))(input)
+}
+
 pub fn macro_pin_direction_encode(input: &str) -> LefRes<&str, u8> {
     ws(alt((
         map(tag("INPUT"), |_| 0),
@@ -103,11 +111,71 @@ pub fn macro_pin_port_class_encode(input: &str) -> LefRes<&str, u8> {
     )))(input)
 }
 
-// pub fn antenna_model_encode(input: &str) -> LefRes<&str, u8> {
-//     ws(alt((
-//         map(tag("OXIDE1"), |_| 0),
-//         map(tag("OXIDE2"), |_| 1),
-//         map(tag("OXIDE3"), |_| 2),
-//         map(tag("OXIDE4"), |_| 3),
-//     )))(input)
-// }
+pub fn layer_type_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((
+        map(tag("ROUTING"), |_| 0),
+        map(tag("CUT"), |_| 1),
+        map(tag("MASTERSLICE"), |_| 2),
+        map(tag("OVERLAP"), |_| 3),
+        map(tag("IMPLANT"), |_| 4),
+    )))(input)
+}
+
+pub fn layer_direction_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((
+        map(tag("HORIZONTAL"), |_| 0),
+        map(tag("VERTICAL"), |_| 1),
+        map(tag("DIAG45"), |_| 2),
+        map(tag("DIAG135"), |_| 3),
+    )))(input)
+}
+
+pub fn enclosure_position_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((map(tag("ABOVE"), |_| 0), map(tag("BELOW"), |_| 1))))(input)
+}
+
+pub fn min_step_type_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((
+        map(tag("INSIDECORNER"), |_| 0),
+        map(tag("OUTSIDECORNER"), |_| 1),
+        map(tag("STEP"), |_| 2),
+    )))(input)
+}
+
+pub fn property_object_type_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((
+        map(tag("LIBRARY"), |_| 0),
+        map(tag("LAYER"), |_| 1),
+        map(tag("VIARULE"), |_| 3),
+        map(tag("VIA"), |_| 2),
+        map(tag("NONDEFAULTRULE"), |_| 4),
+        map(tag("MACRO"), |_| 5),
+        map(tag("PIN"), |_| 6),
+    )))(input)
+}
+
+pub fn property_type_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((
+        map(tag("INTEGER"), |_| 0),
+        map(tag("REAL"), |_| 1),
+        map(tag("STRING"), |_| 2),
+    )))(input)
+}
+
+pub fn edge_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((
+        map(tag("RIGHT"), |_| 0),
+        map(tag("LEFT"), |_| 1),
+        map(tag("TOP"), |_| 2),
+        map(tag("BOTTOM"), |_| 3),
+    )))(input)
+}
+
+pub fn antenna_model_encode(input: &str) -> LefRes<&str, u8> {
+    ws(alt((
+        map(tag("OXIDE1"), |_| 0),
+        map(tag("OXIDE2"), |_| 1),
+        map(tag("OXIDE3"), |_| 2),
+        map(tag("OXIDE4"), |_| 3),
+    )))(input)
+}
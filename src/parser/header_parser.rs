@@ -1,40 +1,167 @@
-use crate::LefRes;
+use crate::{model::LefData, LefRes};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::combinator::opt;
+use nom::character::complete::{anychar, char};
+use nom::combinator::{map, opt, recognize};
+use nom::error::context;
 use nom::multi::many0;
-use nom::sequence::{delimited, tuple};
+use nom::sequence::{delimited, pair, preceded, tuple};
 
-use super::base::{float, ws};
-use super::common::lef_comment;
+use super::base::{float, positive_number, tstring, ws};
 
-pub fn header_section(
-    input: &str,
-) -> LefRes<&str, (Vec<()>, Option<f32>, Option<&str>, Option<&str>)> {
-    tuple((
-        many0(lef_comment),
-        opt(version_num),
-        opt(busbit_chars),
-        opt(divider_char),
-    ))(input)
+pub enum HeaderStatement {
+    Version(f64),
+    BusBitChars(String),
+    DividerChar(String),
+    NamesCaseSensitive(bool),
+    NoWireExtensionAtPin(bool),
+    ClearanceMeasure(u8),
+    UseMinSpacing(bool),
+    MaxViaStack((u32, Option<(String, String)>)),
+    FixedMask,
+    AntennaInputGateArea(f64),
+    AntennaInOutDiffArea(f64),
+    AntennaOutputDiffArea(f64),
+    SameNetSpacing(Vec<(String, String, f64, bool)>),
+}
+
+// header statements and library options, in any order
+pub fn header_statement(input: &str) -> LefRes<&str, HeaderStatement> {
+    context(
+        "Header Statement",
+        alt((
+            map(version_num, HeaderStatement::Version),
+            map(busbit_chars, |x| {
+                HeaderStatement::BusBitChars(x.to_string())
+            }),
+            map(divider_char, |x| {
+                HeaderStatement::DividerChar(x.to_string())
+            }),
+            map(
+                delimited(ws(tag("NAMESCASESENSITIVE")), on_off, ws(tag(";"))),
+                HeaderStatement::NamesCaseSensitive,
+            ),
+            map(
+                delimited(ws(tag("NOWIREEXTENSIONATPIN")), on_off, ws(tag(";"))),
+                HeaderStatement::NoWireExtensionAtPin,
+            ),
+            map(
+                delimited(
+                    ws(tag("CLEARANCEMEASURE")),
+                    alt((
+                        map(ws(tag("MAXXY")), |_| 0),
+                        map(ws(tag("EUCLIDEAN")), |_| 1),
+                    )),
+                    ws(tag(";")),
+                ),
+                HeaderStatement::ClearanceMeasure,
+            ),
+            map(
+                delimited(
+                    tuple((ws(tag("USEMINSPACING")), ws(tag("OBS")))),
+                    on_off,
+                    ws(tag(";")),
+                ),
+                HeaderStatement::UseMinSpacing,
+            ),
+            map(
+                delimited(
+                    ws(tag("MAXVIASTACK")),
+                    tuple((
+                        positive_number,
+                        opt(preceded(ws(tag("RANGE")), tuple((tstring, tstring)))),
+                    )),
+                    ws(tag(";")),
+                ),
+                |(value, range)| {
+                    HeaderStatement::MaxViaStack((
+                        value,
+                        range.map(|(x, y)| (x.to_string(), y.to_string())),
+                    ))
+                },
+            ),
+            map(tuple((ws(tag("FIXEDMASK")), ws(tag(";")))), |_| {
+                HeaderStatement::FixedMask
+            }),
+            map(
+                delimited(ws(tag("ANTENNAINPUTGATEAREA")), float, ws(tag(";"))),
+                HeaderStatement::AntennaInputGateArea,
+            ),
+            map(
+                delimited(ws(tag("ANTENNAINOUTDIFFAREA")), float, ws(tag(";"))),
+                HeaderStatement::AntennaInOutDiffArea,
+            ),
+            map(
+                delimited(ws(tag("ANTENNAOUTPUTDIFFAREA")), float, ws(tag(";"))),
+                HeaderStatement::AntennaOutputDiffArea,
+            ),
+            map(same_net_spacing, HeaderStatement::SameNetSpacing),
+        )),
+    )(input)
+}
+
+pub fn header_fold(lef: &mut LefData, statement: HeaderStatement) {
+    match statement {
+        HeaderStatement::Version(x) => lef.version = x,
+        HeaderStatement::BusBitChars(x) => lef.busbitchar = x,
+        HeaderStatement::DividerChar(x) => lef.dividechar = x,
+        HeaderStatement::NamesCaseSensitive(x) => lef.names_case_sensitive = Some(x),
+        HeaderStatement::NoWireExtensionAtPin(x) => lef.no_wire_extension_at_pin = Some(x),
+        HeaderStatement::ClearanceMeasure(x) => lef.clearance_measure = Some(x),
+        HeaderStatement::UseMinSpacing(x) => lef.use_min_spacing = Some(x),
+        HeaderStatement::MaxViaStack(x) => lef.max_via_stack = Some(x),
+        HeaderStatement::FixedMask => lef.fixed_mask = true,
+        HeaderStatement::AntennaInputGateArea(x) => lef.antenna_input_gate_area = Some(x),
+        HeaderStatement::AntennaInOutDiffArea(x) => lef.antenna_inout_diff_area = Some(x),
+        HeaderStatement::AntennaOutputDiffArea(x) => lef.antenna_output_diff_area = Some(x),
+        HeaderStatement::SameNetSpacing(x) => lef.same_net_spacing.extend(x),
+    }
+}
+
+// SPACING
+//   SAMENET layerName layerName minSpace [STACK] ; ...
+// END SPACING
+fn same_net_spacing(input: &str) -> LefRes<&str, Vec<(String, String, f64, bool)>> {
+    delimited(
+        ws(tag("SPACING")),
+        many0(delimited(
+            ws(tag("SAMENET")),
+            tuple((tstring, tstring, float, opt(ws(tag("STACK"))))),
+            ws(tag(";")),
+        )),
+        tuple((ws(tag("END")), ws(tag("SPACING")))),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            data.into_iter()
+                .map(|(x, y, spacing, stack)| {
+                    (x.to_string(), y.to_string(), spacing, stack.is_some())
+                })
+                .collect(),
+        )
+    })
+}
+
+fn on_off(input: &str) -> LefRes<&str, bool> {
+    alt((map(ws(tag("ON")), |_| true), map(ws(tag("OFF")), |_| false)))(input)
 }
 
 fn divider_char(input: &str) -> LefRes<&str, &str> {
     delimited(
         ws(tag("DIVIDERCHAR")),
-        alt((
-            ws(tag("\"/\"")),
-            ws(tag("\"\\\"")),
-            ws(tag("\"%\"")),
-            ws(tag("\"$\"")),
-        )),
+        ws(delimited(char('"'), recognize(anychar), char('"'))),
         ws(tag(";")),
     )(input)
 }
 fn busbit_chars(input: &str) -> LefRes<&str, &str> {
     delimited(
         ws(tag("BUSBITCHARS")),
-        alt((ws(tag("\"[]\"")), ws(tag("\"{}\"")), ws(tag("\"<>\"")))),
+        ws(delimited(
+            char('"'),
+            recognize(pair(anychar, anychar)),
+            char('"'),
+        )),
         ws(tag(";")),
     )(input)
 }
@@ -44,7 +171,7 @@ fn version_num(
     input: &str,
 ) -> LefRes<
     &str,
-    f32, // version number
+    f64, // version number
 > {
     delimited(ws(tag("VERSION")), float, ws(tag(";")))(input)
 }
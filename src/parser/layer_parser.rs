@@ -0,0 +1,776 @@
+use crate::{
+    model::{
+        CutSpacingRule, EndOfLineSpacing, LayerEnclosure, LayerSpacing, LefLayer, MinStep,
+        MinimumCut, PrlSpacingTable, PropValue, TwoWidthsSpacingTable,
+    },
+    LefRes,
+};
+use nom::branch::alt;
+use nom::bytes::complete::is_not;
+use nom::bytes::complete::tag;
+use nom::character::complete::char;
+use nom::combinator::{map, not, opt, recognize, verify};
+use nom::error::context;
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, preceded, tuple};
+
+use super::antenna_parser::{layer_antenna_fold, layer_antenna_statement, LayerAntennaStatement};
+use super::base::{float, positive_number, quoted, tstring, ws};
+use super::encoder::{
+    enclosure_position_encode, layer_direction_encode, layer_type_encode, min_step_type_encode,
+};
+use super::lef58_parser::lef58_rules;
+use super::property_parser::property_statement;
+
+// keywords of the statements below. a malformed one is an error, not an unparsed statement
+const LAYER_KEYWORDS: &[&str] = &[
+    "DIRECTION",
+    "PITCH",
+    "OFFSET",
+    "WIDTH",
+    "MINWIDTH",
+    "MAXWIDTH",
+    "AREA",
+    "SPACINGTABLE",
+    "SPACING",
+    "ENCLOSURE",
+    "THICKNESS",
+    "HEIGHT",
+    "RESISTANCE",
+    "CAPACITANCE",
+    "EDGECAPACITANCE",
+    "MINSTEP",
+    "MINIMUMCUT",
+    "ACCURRENTDENSITY",
+    "DCCURRENTDENSITY",
+    "MINENCLOSEDAREA",
+    "PROTRUSIONWIDTH",
+    "MINIMUMDENSITY",
+    "MAXIMUMDENSITY",
+    "DENSITYCHECKWINDOW",
+    "DENSITYCHECKSTEP",
+    "PROPERTY",
+    "ANTENNAMODEL",
+    "ANTENNAAREARATIO",
+    "ANTENNADIFFAREARATIO",
+    "ANTENNACUMAREARATIO",
+    "ANTENNACUMDIFFAREARATIO",
+    "ANTENNAAREAFACTOR",
+    "ANTENNASIDEAREARATIO",
+    "ANTENNADIFFSIDEAREARATIO",
+    "ANTENNACUMSIDEAREARATIO",
+    "ANTENNACUMDIFFSIDEAREARATIO",
+    "ANTENNASIDEAREAFACTOR",
+];
+
+// statements allowed inside a LAYER block. They may come in any order.
+enum LayerStatement {
+    Direction(u8),
+    Pitch((f64, f64)),
+    Offset((f64, f64)),
+    Width(f64),
+    MinWidth(f64),
+    MaxWidth(f64),
+    Area(f64),
+    Spacing(LayerSpacing),
+    PrlSpacingTable(PrlSpacingTable),
+    TwoWidthsSpacingTable(TwoWidthsSpacingTable),
+    InfluenceSpacingTable(Vec<(f64, f64, f64)>),
+    Enclosure(LayerEnclosure),
+    Thickness(f64),
+    Height(f64),
+    Resistance(f64),
+    Capacitance(f64),
+    EdgeCapacitance(f64),
+    MinStep(MinStep),
+    MinimumCut(MinimumCut),
+    MinEnclosedArea((f64, Option<f64>)),
+    ProtrusionWidth((f64, f64, f64)),
+    MinimumDensity(f64),
+    MaximumDensity(f64),
+    DensityCheckWindow((f64, f64)),
+    DensityCheckStep(f64),
+    Property(Vec<(String, PropValue)>),
+    Antenna(LayerAntennaStatement),
+    Unparsed(String),
+}
+
+pub fn layer_parser(input: &str) -> LefRes<&str, LefLayer> {
+    context(
+        "Layer Statement",
+        tuple((
+            preceded(ws(tag("LAYER")), tstring),
+            delimited(ws(tag("TYPE")), layer_type_encode, ws(tag(";"))),
+            many0(layer_statement),
+            preceded(ws(tag("END")), tstring),
+        )),
+    )(input)
+    .map(|(res, data)| {
+        let mut layer = LefLayer {
+            layer_name: data.0.to_string(),
+            layer_type: data.1,
+            direction: None,
+            pitch: None,
+            offset: None,
+            width: None,
+            min_width: None,
+            max_width: None,
+            area: None,
+            spacing: Vec::new(),
+            prl_spacing_table: None,
+            two_widths_spacing_table: None,
+            influence_spacing_table: Vec::new(),
+            enclosure: Vec::new(),
+            thickness: None,
+            height: None,
+            resistance: None,
+            capacitance: None,
+            edge_capacitance: None,
+            min_step: Vec::new(),
+            minimum_cut: Vec::new(),
+            min_enclosed_area: Vec::new(),
+            protrusion_width: None,
+            minimum_density: None,
+            maximum_density: None,
+            density_check_window: None,
+            density_check_step: None,
+            property: Vec::new(),
+            lef58_rule: Vec::new(),
+            antenna: Vec::new(),
+            unparsed: Vec::new(),
+            source_file: None,
+            comments: Vec::new(),
+        };
+        let mut antenna_model = 0;
+        for statement in data.2 {
+            match statement {
+                LayerStatement::Direction(x) => layer.direction = Some(x),
+                LayerStatement::Pitch(x) => layer.pitch = Some(x),
+                LayerStatement::Offset(x) => layer.offset = Some(x),
+                LayerStatement::Width(x) => layer.width = Some(x),
+                LayerStatement::MinWidth(x) => layer.min_width = Some(x),
+                LayerStatement::MaxWidth(x) => layer.max_width = Some(x),
+                LayerStatement::Area(x) => layer.area = Some(x),
+                LayerStatement::Spacing(x) => layer.spacing.push(x),
+                LayerStatement::PrlSpacingTable(x) => layer.prl_spacing_table = Some(x),
+                LayerStatement::TwoWidthsSpacingTable(x) => {
+                    layer.two_widths_spacing_table = Some(x)
+                }
+                LayerStatement::InfluenceSpacingTable(x) => layer.influence_spacing_table = x,
+                LayerStatement::Enclosure(x) => layer.enclosure.push(x),
+                LayerStatement::Thickness(x) => layer.thickness = Some(x),
+                LayerStatement::Height(x) => layer.height = Some(x),
+                LayerStatement::Resistance(x) => layer.resistance = Some(x),
+                LayerStatement::Capacitance(x) => layer.capacitance = Some(x),
+                LayerStatement::EdgeCapacitance(x) => layer.edge_capacitance = Some(x),
+                LayerStatement::MinStep(x) => layer.min_step.push(x),
+                LayerStatement::MinimumCut(x) => layer.minimum_cut.push(x),
+                LayerStatement::MinEnclosedArea(x) => layer.min_enclosed_area.push(x),
+                LayerStatement::ProtrusionWidth(x) => layer.protrusion_width = Some(x),
+                LayerStatement::MinimumDensity(x) => layer.minimum_density = Some(x),
+                LayerStatement::MaximumDensity(x) => layer.maximum_density = Some(x),
+                LayerStatement::DensityCheckWindow(x) => layer.density_check_window = Some(x),
+                LayerStatement::DensityCheckStep(x) => layer.density_check_step = Some(x),
+                LayerStatement::Property(x) => layer.property.extend(x),
+                LayerStatement::Antenna(x) => {
+                    layer_antenna_fold(&mut layer.antenna, &mut antenna_model, x)
+                }
+                LayerStatement::Unparsed(x) => layer.unparsed.push(x),
+            }
+        }
+        layer.lef58_rule = lef58_rules(&layer.property);
+        (res, layer)
+    })
+}
+
+fn layer_statement(input: &str) -> LefRes<&str, LayerStatement> {
+    context(
+        "Layer Property Statement",
+        alt((
+            map(
+                delimited(ws(tag("DIRECTION")), layer_direction_encode, ws(tag(";"))),
+                LayerStatement::Direction,
+            ),
+            map(
+                delimited(ws(tag("PITCH")), distance_pair, ws(tag(";"))),
+                LayerStatement::Pitch,
+            ),
+            map(
+                delimited(ws(tag("OFFSET")), distance_pair, ws(tag(";"))),
+                LayerStatement::Offset,
+            ),
+            map(
+                delimited(ws(tag("WIDTH")), float, ws(tag(";"))),
+                LayerStatement::Width,
+            ),
+            map(
+                delimited(ws(tag("MINWIDTH")), float, ws(tag(";"))),
+                LayerStatement::MinWidth,
+            ),
+            map(
+                delimited(ws(tag("MAXWIDTH")), float, ws(tag(";"))),
+                LayerStatement::MaxWidth,
+            ),
+            map(
+                delimited(ws(tag("AREA")), float, ws(tag(";"))),
+                LayerStatement::Area,
+            ),
+            spacing_table_statement,
+            map(spacing_statement, LayerStatement::Spacing),
+            map(enclosure_statement, LayerStatement::Enclosure),
+            map(
+                delimited(ws(tag("THICKNESS")), float, ws(tag(";"))),
+                LayerStatement::Thickness,
+            ),
+            map(
+                delimited(ws(tag("HEIGHT")), float, ws(tag(";"))),
+                LayerStatement::Height,
+            ),
+            map(
+                delimited(
+                    tuple((ws(tag("RESISTANCE")), ws(tag("RPERSQ")))),
+                    float,
+                    ws(tag(";")),
+                ),
+                LayerStatement::Resistance,
+            ),
+            // cut layers give the resistance per cut
+            map(
+                delimited(ws(tag("RESISTANCE")), float, ws(tag(";"))),
+                LayerStatement::Resistance,
+            ),
+            map(
+                delimited(
+                    tuple((ws(tag("CAPACITANCE")), ws(tag("CPERSQDIST")))),
+                    float,
+                    ws(tag(";")),
+                ),
+                LayerStatement::Capacitance,
+            ),
+            map(
+                delimited(ws(tag("EDGECAPACITANCE")), float, ws(tag(";"))),
+                LayerStatement::EdgeCapacitance,
+            ),
+            layer_rule_statement,
+            map(property_statement, LayerStatement::Property),
+            map(layer_antenna_statement, LayerStatement::Antenna),
+            // any other keyword is kept as written instead of failing the library
+            map(
+                preceded(
+                    not(verify(ws(tstring), |x: &str| LAYER_KEYWORDS.contains(&x))),
+                    unknown_statement,
+                ),
+                |x| LayerStatement::Unparsed(x.to_string()),
+            ),
+        )),
+    )(input)
+}
+
+// ACCURRENTDENSITY {PEAK | AVERAGE | RMS} { value ; | FREQUENCY ... ; ... TABLEENTRIES ... ; }
+// DCCURRENTDENSITY AVERAGE { value ; | WIDTH ... ; ... TABLEENTRIES ... ; }
+fn current_density_statement(input: &str) -> LefRes<&str, &str> {
+    ws(recognize(tuple((
+        alt((tag("ACCURRENTDENSITY"), tag("DCCURRENTDENSITY"))),
+        tstring,
+        alt((
+            recognize(tuple((float, ws(tag(";"))))),
+            recognize(tuple((
+                many0(verify(unknown_statement, |x: &str| {
+                    !x.starts_with("TABLEENTRIES")
+                })),
+                verify(unknown_statement, |x: &str| x.starts_with("TABLEENTRIES")),
+            ))),
+        )),
+    ))))(input)
+}
+
+// KEYWORD ... ; with any keyword but END
+fn unknown_statement(input: &str) -> LefRes<&str, &str> {
+    ws(recognize(tuple((
+        verify(tstring, |x: &str| x != "END"),
+        many0(alt((quoted, is_not(";\"")))),
+        char(';'),
+    ))))(input)
+}
+
+fn layer_rule_statement(input: &str) -> LefRes<&str, LayerStatement> {
+    context(
+        "Layer Rule Statement",
+        alt((
+            map(min_step_statement, LayerStatement::MinStep),
+            map(minimum_cut_statement, LayerStatement::MinimumCut),
+            map(current_density_statement, |x| {
+                LayerStatement::Unparsed(x.trim_end().to_string())
+            }),
+            map(
+                delimited(
+                    ws(tag("MINENCLOSEDAREA")),
+                    tuple((float, opt(preceded(ws(tag("WIDTH")), float)))),
+                    ws(tag(";")),
+                ),
+                LayerStatement::MinEnclosedArea,
+            ),
+            map(
+                delimited(
+                    ws(tag("PROTRUSIONWIDTH")),
+                    tuple((
+                        float,
+                        preceded(ws(tag("LENGTH")), float),
+                        preceded(ws(tag("WIDTH")), float),
+                    )),
+                    ws(tag(";")),
+                ),
+                LayerStatement::ProtrusionWidth,
+            ),
+            map(
+                delimited(ws(tag("MINIMUMDENSITY")), float, ws(tag(";"))),
+                LayerStatement::MinimumDensity,
+            ),
+            map(
+                delimited(ws(tag("MAXIMUMDENSITY")), float, ws(tag(";"))),
+                LayerStatement::MaximumDensity,
+            ),
+            map(
+                delimited(
+                    ws(tag("DENSITYCHECKWINDOW")),
+                    tuple((float, float)),
+                    ws(tag(";")),
+                ),
+                LayerStatement::DensityCheckWindow,
+            ),
+            map(
+                delimited(ws(tag("DENSITYCHECKSTEP")), float, ws(tag(";"))),
+                LayerStatement::DensityCheckStep,
+            ),
+        )),
+    )(input)
+}
+
+// MINSTEP minStepLength [[INSIDECORNER | OUTSIDECORNER | STEP] [LENGTHSUM maxLength]
+//                        | [MAXEDGES maxEdges]] ;
+fn min_step_statement(input: &str) -> LefRes<&str, MinStep> {
+    context(
+        "Layer MinStep Statement",
+        delimited(
+            ws(tag("MINSTEP")),
+            tuple((
+                float,
+                opt(min_step_type_encode),
+                opt(preceded(ws(tag("LENGTHSUM")), float)),
+                opt(preceded(ws(tag("MAXEDGES")), positive_number)),
+            )),
+            ws(tag(";")),
+        ),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            MinStep {
+                min_step_length: data.0,
+                step_type: data.1,
+                length_sum: data.2,
+                max_edges: data.3,
+            },
+        )
+    })
+}
+
+// MINIMUMCUT numCuts WIDTH width [WITHIN cutDistance] [FROMABOVE | FROMBELOW]
+//            [LENGTH length WITHIN distance | AREA area [WITHIN distance]] ;
+fn minimum_cut_statement(input: &str) -> LefRes<&str, MinimumCut> {
+    context(
+        "Layer MinimumCut Statement",
+        delimited(
+            ws(tag("MINIMUMCUT")),
+            tuple((
+                positive_number,
+                preceded(ws(tag("WIDTH")), float),
+                opt(preceded(ws(tag("WITHIN")), float)),
+                opt(alt((
+                    map(ws(tag("FROMABOVE")), |_| 0),
+                    map(ws(tag("FROMBELOW")), |_| 1),
+                ))),
+                opt(tuple((
+                    preceded(ws(tag("LENGTH")), float),
+                    preceded(ws(tag("WITHIN")), float),
+                ))),
+                opt(tuple((
+                    preceded(ws(tag("AREA")), float),
+                    opt(preceded(ws(tag("WITHIN")), float)),
+                ))),
+            )),
+            ws(tag(";")),
+        ),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            MinimumCut {
+                num_cuts: data.0,
+                width: data.1,
+                within: data.2,
+                from: data.3,
+                length: data.4,
+                area: data.5,
+            },
+        )
+    })
+}
+
+// PITCH and OFFSET accept either one distance or a x/y pair.
+fn distance_pair(input: &str) -> LefRes<&str, (f64, f64)> {
+    tuple((float, opt(float)))(input).map(|(res, data)| (res, (data.0, data.1.unwrap_or(data.0))))
+}
+
+// SPACING minSpacing [SAMENET [PGONLY] | RANGE minWidth maxWidth
+//                     | LENGTHTHRESHOLD maxLength | ENDOFLINE ...] ;
+// cut layers: SPACING cutSpacing [CENTERTOCENTER] [SAMENET] [LAYER secondLayerName [STACK]
+//                     | ADJACENTCUTS {2 | 3 | 4} WITHIN cutWithin [EXCEPTSAMEPGNET]
+//                     | PARALLELOVERLAP | AREA cutArea] ;
+fn spacing_statement(input: &str) -> LefRes<&str, LayerSpacing> {
+    context(
+        "Layer Spacing Statement",
+        delimited(
+            ws(tag("SPACING")),
+            tuple((
+                float,
+                map(opt(ws(tag("CENTERTOCENTER"))), |x| x.is_some()),
+                opt(preceded(
+                    ws(tag("SAMENET")),
+                    map(opt(ws(tag("PGONLY"))), |x| x.is_some()),
+                )),
+                opt(cut_spacing_rule),
+                opt(preceded(ws(tag("RANGE")), tuple((float, float)))),
+                opt(preceded(ws(tag("LENGTHTHRESHOLD")), float)),
+                opt(end_of_line_spacing),
+            )),
+            ws(tag(";")),
+        ),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            LayerSpacing {
+                min_spacing: data.0,
+                range: data.4,
+                length_threshold: data.5,
+                end_of_line: data.6,
+                same_net: data.2,
+                center_to_center: data.1,
+                cut_rule: data.3,
+            },
+        )
+    })
+}
+
+fn cut_spacing_rule(input: &str) -> LefRes<&str, CutSpacingRule> {
+    alt((
+        map(
+            preceded(
+                ws(tag("LAYER")),
+                tuple((tstring, map(opt(ws(tag("STACK"))), |x| x.is_some()))),
+            ),
+            |(name, stack)| CutSpacingRule::Layer((name.to_string(), stack)),
+        ),
+        map(
+            tuple((
+                preceded(ws(tag("ADJACENTCUTS")), positive_number),
+                preceded(ws(tag("WITHIN")), float),
+                map(opt(ws(tag("EXCEPTSAMEPGNET"))), |x| x.is_some()),
+            )),
+            CutSpacingRule::AdjacentCuts,
+        ),
+        map(ws(tag("PARALLELOVERLAP")), |_| {
+            CutSpacingRule::ParallelOverlap
+        }),
+        map(preceded(ws(tag("AREA")), float), CutSpacingRule::Area),
+    ))(input)
+}
+
+// ENDOFLINE eolWidth WITHIN eolWithin [PARALLELEDGE parSpace WITHIN parWithin [TWOEDGES]]
+pub fn end_of_line_spacing(input: &str) -> LefRes<&str, EndOfLineSpacing> {
+    tuple((
+        preceded(ws(tag("ENDOFLINE")), float),
+        preceded(ws(tag("WITHIN")), float),
+        opt(tuple((
+            preceded(ws(tag("PARALLELEDGE")), float),
+            preceded(ws(tag("WITHIN")), float),
+            map(opt(ws(tag("TWOEDGES"))), |x| x.is_some()),
+        ))),
+    ))(input)
+    .map(|(res, data)| {
+        (
+            res,
+            EndOfLineSpacing {
+                eol_width: data.0,
+                eol_within: data.1,
+                parallel_edge: data.2,
+            },
+        )
+    })
+}
+
+fn spacing_table_statement(input: &str) -> LefRes<&str, LayerStatement> {
+    context(
+        "Layer Spacing Table Statement",
+        delimited(
+            ws(tag("SPACINGTABLE")),
+            alt((
+                map(
+                    preceded(
+                        ws(tag("PARALLELRUNLENGTH")),
+                        tuple((
+                            many1(float),
+                            many1(preceded(ws(tag("WIDTH")), tuple((float, many1(float))))),
+                        )),
+                    ),
+                    |data| {
+                        let (width, spacing) = data.1.into_iter().unzip();
+                        LayerStatement::PrlSpacingTable(PrlSpacingTable {
+                            length: data.0,
+                            width,
+                            spacing,
+                        })
+                    },
+                ),
+                map(
+                    preceded(
+                        ws(tag("TWOWIDTHS")),
+                        many1(preceded(
+                            ws(tag("WIDTH")),
+                            tuple((float, opt(preceded(ws(tag("PRL")), float)), many1(float))),
+                        )),
+                    ),
+                    |data| {
+                        let mut table = TwoWidthsSpacingTable {
+                            width: Vec::new(),
+                            prl: Vec::new(),
+                            spacing: Vec::new(),
+                        };
+                        for row in data {
+                            table.width.push(row.0);
+                            table.prl.push(row.1);
+                            table.spacing.push(row.2);
+                        }
+                        LayerStatement::TwoWidthsSpacingTable(table)
+                    },
+                ),
+                map(
+                    preceded(
+                        ws(tag("INFLUENCE")),
+                        many1(tuple((
+                            preceded(ws(tag("WIDTH")), float),
+                            preceded(ws(tag("WITHIN")), float),
+                            preceded(ws(tag("SPACING")), float),
+                        ))),
+                    ),
+                    LayerStatement::InfluenceSpacingTable,
+                ),
+            )),
+            ws(tag(";")),
+        ),
+    )(input)
+}
+
+// ENCLOSURE [ABOVE | BELOW] overhang1 overhang2 [WIDTH minWidth] ;
+fn enclosure_statement(input: &str) -> LefRes<&str, LayerEnclosure> {
+    context(
+        "Layer Enclosure Statement",
+        delimited(
+            ws(tag("ENCLOSURE")),
+            tuple((
+                opt(enclosure_position_encode),
+                float,
+                float,
+                opt(preceded(ws(tag("WIDTH")), float)),
+            )),
+            ws(tag(";")),
+        ),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            LayerEnclosure {
+                position: data.0,
+                overhang: (data.1, data.2),
+                min_width: data.3,
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_routing_layer() {
+        let test_str = "LAYER metal2
+  TYPE ROUTING ;
+  DIRECTION VERTICAL ;
+  PITCH 0.2 ;
+  OFFSET 0.1 0.05 ;
+  WIDTH 0.07 ;
+  MINWIDTH 0.07 ;
+  MAXWIDTH 4.0 ;
+  AREA 0.0196 ;
+  SPACING 0.07 ;
+  SPACING 0.3 RANGE 1.5 4.0 ;
+  THICKNESS 0.14 ;
+  HEIGHT 0.5 ;
+  RESISTANCE RPERSQ 0.38 ;
+  CAPACITANCE CPERSQDIST 0.000119 ;
+  EDGECAPACITANCE 0.000028 ;
+END metal2";
+        let (_, layer) = layer_parser(test_str).unwrap();
+        assert_eq!(layer.layer_name, "metal2");
+        assert_eq!(layer.direction, Some(1));
+        assert_eq!(layer.pitch, Some((0.2, 0.2)));
+        assert_eq!(layer.offset, Some((0.1, 0.05)));
+        assert_eq!(layer.spacing.len(), 2);
+        assert_eq!(layer.spacing[1].range, Some((1.5, 4.0)));
+        assert_eq!(layer.resistance, Some(0.38));
+        assert_eq!(layer.edge_capacitance, Some(0.000028));
+    }
+
+    #[test]
+    fn test_cut_layer() {
+        let test_str = "LAYER via1
+  TYPE CUT ;
+  SPACING 0.08 ;
+  WIDTH 0.07 ;
+  ENCLOSURE BELOW 0 0.035 ;
+  ENCLOSURE ABOVE 0.005 0.03 WIDTH 0.2 ;
+  RESISTANCE 5.0 ;
+END via1";
+        let (_, layer) = layer_parser(test_str).unwrap();
+        assert_eq!(layer.layer_type, 1);
+        assert_eq!(layer.resistance, Some(5.0));
+        assert_eq!(layer.enclosure.len(), 2);
+        assert_eq!(layer.enclosure[1].position, Some(0));
+        assert_eq!(layer.enclosure[1].min_width, Some(0.2));
+
+        let test_str = "LAYER via2
+  TYPE CUT ;
+  SPACING 0.08 ADJACENTCUTS 3 WITHIN 0.1 ;
+  SPACING 0.1 CENTERTOCENTER SAMENET ;
+  SPACING 0.05 LAYER via1 STACK ;
+  SPACING 0.09 PARALLELOVERLAP ;
+  SPACING 0.12 AREA 0.02 ;
+  SPACING 0.07 ;
+  DCCURRENTDENSITY AVERAGE 0.5 ;
+  ACCURRENTDENSITY RMS
+    FREQUENCY 1E6 100E6 ;
+    TABLEENTRIES 0.5E-6 0.4E-6 ;
+  MINIMUMCUT 2 WIDTH 0.4 AREA 0.3 ;
+END via2";
+        let (_, layer) = layer_parser(test_str).unwrap();
+        assert_eq!(layer.spacing.len(), 6);
+        assert!(matches!(
+            layer.spacing[0].cut_rule,
+            Some(CutSpacingRule::AdjacentCuts((3, x, false))) if x == 0.1
+        ));
+        assert!(layer.spacing[1].center_to_center);
+        assert_eq!(layer.spacing[1].same_net, Some(false));
+        assert!(matches!(
+            &layer.spacing[2].cut_rule,
+            Some(CutSpacingRule::Layer((x, true))) if x == "via1"
+        ));
+        assert!(matches!(
+            layer.spacing[3].cut_rule,
+            Some(CutSpacingRule::ParallelOverlap)
+        ));
+        assert_eq!(layer.required_spacing(0.07, 0.07, 0.0), Some(0.07));
+        assert_eq!(layer.unparsed.len(), 2);
+        assert!(layer.unparsed[1].starts_with("ACCURRENTDENSITY RMS"));
+        assert!(layer.unparsed[1].ends_with("0.4E-6 ;"));
+        assert_eq!(layer.minimum_cut[0].area, Some((0.3, None)));
+
+        // only unknown keywords are skipped, known ones must parse
+        let test_str = "LAYER via3
+  TYPE CUT ;
+  FOO 1 \"a;b\" ;
+  WIDTH 0.07 ;
+END via3";
+        let (_, layer) = layer_parser(test_str).unwrap();
+        assert_eq!(layer.unparsed, vec!["FOO 1 \"a;b\" ;"]);
+        assert_eq!(layer.width, Some(0.07));
+        assert!(layer_parser("LAYER via3 TYPE CUT ; WIDTH abc ; END via3").is_err());
+        assert!(layer_parser("LAYER via3 TYPE CUT ; SPACING 0.1 FOO ; END via3").is_err());
+    }
+
+    #[test]
+    fn test_spacing_table() {
+        let test_str = "LAYER metal1
+  TYPE ROUTING ;
+  SPACINGTABLE
+    PARALLELRUNLENGTH 0.00 0.50 3.00 5.00
+    WIDTH 0.00 0.05 0.05 0.05 0.05
+    WIDTH 0.26 0.05 0.10 0.10 0.10
+    WIDTH 1.50 0.05 0.10 0.50 0.50
+    WIDTH 4.50 0.05 0.10 0.50 1.50 ;
+  SPACINGTABLE INFLUENCE
+    WIDTH 1.5 WITHIN 0.5 SPACING 0.5
+    WIDTH 4.5 WITHIN 1.5 SPACING 1.5 ;
+END metal1";
+        let (_, layer) = layer_parser(test_str).unwrap();
+        assert_eq!(layer.influence_spacing_table.len(), 2);
+        assert_eq!(layer.required_spacing(0.05, 0.05, 10.0), Some(0.05));
+        assert_eq!(layer.required_spacing(0.05, 2.0, 0.2), Some(0.05));
+        assert_eq!(layer.required_spacing(0.05, 2.0, 4.0), Some(0.5));
+        assert_eq!(layer.required_spacing(5.0, 0.05, 3.0), Some(0.1));
+        assert_eq!(layer.required_spacing(5.0, 0.05, 3.5), Some(0.5));
+        assert_eq!(layer.required_spacing(5.0, 0.05, 6.0), Some(1.5));
+        // INFLUENCE needs the distance to the wide wire, the width lookup ignores it
+        assert_eq!(layer.required_spacing(5.0, 0.05, 0.2), Some(0.05));
+        assert_eq!(layer.influence_spacing(5.0, 1.0), Some(1.5));
+        assert_eq!(layer.influence_spacing(5.0, 0.2), Some(1.5));
+        assert_eq!(layer.influence_spacing(2.0, 0.4), Some(0.5));
+        assert_eq!(layer.influence_spacing(2.0, 0.6), None);
+        assert_eq!(layer.influence_spacing(1.0, 0.1), None);
+
+        let test_str = "LAYER metal2
+  TYPE ROUTING ;
+  SPACINGTABLE TWOWIDTHS
+    WIDTH 0.00 0.05 0.06 0.10
+    WIDTH 0.10 PRL 0.5 0.06 0.08 0.12
+    WIDTH 0.50 0.10 0.12 0.20 ;
+END metal2";
+        let (_, layer) = layer_parser(test_str).unwrap();
+        assert_eq!(layer.required_spacing(0.05, 0.05, 1.0), Some(0.05));
+        assert_eq!(layer.required_spacing(0.2, 0.05, 1.0), Some(0.06));
+        assert_eq!(layer.required_spacing(0.2, 0.05, 0.1), Some(0.05));
+        assert_eq!(layer.required_spacing(0.05, 0.6, 0.1), Some(0.10));
+    }
+
+    #[test]
+    fn test_layer_rules() {
+        let test_str = "LAYER metal1
+  TYPE ROUTING ;
+  SPACING 0.065 ;
+  SPACING 0.09 ENDOFLINE 0.09 WITHIN 0.025 PARALLELEDGE 0.09 WITHIN 0.1 TWOEDGES ;
+  SPACING 0.1 LENGTHTHRESHOLD 0.5 ;
+  MINSTEP 0.05 ;
+  MINSTEP 0.06 INSIDECORNER LENGTHSUM 0.12 ;
+  MINIMUMCUT 2 WIDTH 0.42 WITHIN 0.2 FROMABOVE ;
+  MINIMUMCUT 4 WIDTH 1.0 LENGTH 1.5 WITHIN 2.0 ;
+  MINENCLOSEDAREA 0.2 WIDTH 0.15 ;
+  PROTRUSIONWIDTH 0.3 LENGTH 0.6 WIDTH 1.2 ;
+  MINIMUMDENSITY 20 ;
+  MAXIMUMDENSITY 80 ;
+  DENSITYCHECKWINDOW 100 100 ;
+  DENSITYCHECKSTEP 50 ;
+END metal1";
+        let (_, layer) = layer_parser(test_str).unwrap();
+        let eol = layer.spacing[1].end_of_line.as_ref().unwrap();
+        assert_eq!(eol.eol_within, 0.025);
+        assert_eq!(eol.parallel_edge, Some((0.09, 0.1, true)));
+        assert_eq!(layer.min_step[1].step_type, Some(0));
+        assert_eq!(layer.minimum_cut[0].from, Some(0));
+        assert_eq!(layer.minimum_cut[1].length, Some((1.5, 2.0)));
+        assert_eq!(layer.min_enclosed_area, vec![(0.2, Some(0.15))]);
+        assert_eq!(layer.protrusion_width, Some((0.3, 0.6, 1.2)));
+        assert_eq!(layer.density_check_window, Some((100.0, 100.0)));
+        assert_eq!(layer.required_spacing(0.05, 0.05, 0.2), Some(0.1));
+        assert_eq!(layer.required_spacing(0.05, 0.05, 1.0), Some(0.065));
+    }
+}
@@ -0,0 +1,291 @@
+// sub-parser for the rules that LEF 5.8 extensions hide inside quoted LEF58_* property
+// strings. Every string may hold several rules, each one terminated by `;`.
+use crate::{
+    model::{
+        Lef58CornerSpacing, Lef58CutClass, Lef58EdgeType, Lef58EolKeepout, Lef58Rule, Lef58Spacing,
+        PropValue,
+    },
+    LefRes,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{all_consuming, map, opt};
+use nom::multi::many1;
+use nom::sequence::{preceded, tuple};
+
+use super::base::{float, positive_number, tstring, ws};
+use super::encoder::{edge_encode, layer_direction_encode};
+use super::layer_parser::end_of_line_spacing;
+
+/// decodes the LEF58_* string properties of a layer or macro. Rules that are not
+/// understood are kept as `Lef58Rule::Raw`.
+pub fn lef58_rules(property: &[(String, PropValue)]) -> Vec<Lef58Rule> {
+    let mut rules = Vec::new();
+    for (name, value) in property.iter() {
+        let text = match value {
+            PropValue::String(x) if name.starts_with("LEF58_") => x,
+            _ => continue,
+        };
+        for rule in text.split(';').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            let decoded = match name.as_str() {
+                "LEF58_SPACING" => all_consuming(map(lef58_spacing, Lef58Rule::Spacing))(rule),
+                "LEF58_CUTCLASS" => all_consuming(map(lef58_cutclass, Lef58Rule::CutClass))(rule),
+                "LEF58_TYPE" => all_consuming(map(preceded(ws(tag("TYPE")), tstring), |x| {
+                    Lef58Rule::Type(x.to_string())
+                }))(rule),
+                "LEF58_EOLKEEPOUT" => {
+                    all_consuming(map(lef58_eolkeepout, Lef58Rule::EolKeepout))(rule)
+                }
+                "LEF58_CORNERSPACING" => {
+                    all_consuming(map(lef58_cornerspacing, Lef58Rule::CornerSpacing))(rule)
+                }
+                "LEF58_EDGETYPE" => all_consuming(map(lef58_edgetype, Lef58Rule::EdgeType))(rule),
+                _ => {
+                    rules.push(Lef58Rule::Raw((name.clone(), rule.to_string())));
+                    continue;
+                }
+            };
+            rules.push(match decoded {
+                Ok((_, x)) => x,
+                Err(_) => Lef58Rule::Raw((name.clone(), rule.to_string())),
+            });
+        }
+    }
+    rules
+}
+
+// SPACING minSpacing [CENTERTOCENTER] [SAMENET | SAMEMASK] [LAYER secondLayerName]
+//         [CUTCLASS className] [ENDOFLINE eolWidth WITHIN eolWithin [PARALLELEDGE ...]]
+fn lef58_spacing(input: &str) -> LefRes<&str, Lef58Spacing> {
+    tuple((
+        preceded(ws(tag("SPACING")), float),
+        map(opt(ws(tag("CENTERTOCENTER"))), |x| x.is_some()),
+        map(opt(ws(tag("SAMENET"))), |x| x.is_some()),
+        map(opt(ws(tag("SAMEMASK"))), |x| x.is_some()),
+        opt(preceded(ws(tag("LAYER")), tstring)),
+        opt(preceded(ws(tag("CUTCLASS")), tstring)),
+        opt(end_of_line_spacing),
+    ))(input)
+    .map(|(res, data)| {
+        (
+            res,
+            Lef58Spacing {
+                min_spacing: data.0,
+                center_to_center: data.1,
+                same_net: data.2,
+                same_mask: data.3,
+                layer_name: data.4.map(|x| x.to_string()),
+                cut_class: data.5.map(|x| x.to_string()),
+                end_of_line: data.6,
+            },
+        )
+    })
+}
+
+// CUTCLASS className WIDTH viaWidth [LENGTH viaLength] [CUTS numCut]
+//          [ORIENT {HORIZONTAL | VERTICAL}]
+fn lef58_cutclass(input: &str) -> LefRes<&str, Lef58CutClass> {
+    tuple((
+        preceded(ws(tag("CUTCLASS")), tstring),
+        preceded(ws(tag("WIDTH")), float),
+        opt(preceded(ws(tag("LENGTH")), float)),
+        opt(preceded(ws(tag("CUTS")), positive_number)),
+        opt(preceded(ws(tag("ORIENT")), layer_direction_encode)),
+    ))(input)
+    .map(|(res, data)| {
+        (
+            res,
+            Lef58CutClass {
+                class_name: data.0.to_string(),
+                width: data.1,
+                length: data.2,
+                num_cuts: data.3,
+                orient: data.4,
+            },
+        )
+    })
+}
+
+// EOLKEEPOUT eolWidth EXTENSION backwardExt sideExt forwardExt [CORNERONLY]
+//            [EXCEPTWITHIN lowExcept highExcept]
+fn lef58_eolkeepout(input: &str) -> LefRes<&str, Lef58EolKeepout> {
+    tuple((
+        preceded(ws(tag("EOLKEEPOUT")), float),
+        preceded(ws(tag("EXTENSION")), tuple((float, float, float))),
+        map(opt(ws(tag("CORNERONLY"))), |x| x.is_some()),
+        opt(preceded(ws(tag("EXCEPTWITHIN")), tuple((float, float)))),
+    ))(input)
+    .map(|(res, data)| {
+        (
+            res,
+            Lef58EolKeepout {
+                eol_width: data.0,
+                extension: data.1,
+                corner_only: data.2,
+                except_within: data.3,
+            },
+        )
+    })
+}
+
+// CORNERSPACING {CONVEXCORNER | CONCAVECORNER} [SAMEMASK] [CORNERONLY within]
+//               [EXCEPTEOL eolWidth] {WIDTH width SPACING spacing [verticalSpacing]}...
+fn lef58_cornerspacing(input: &str) -> LefRes<&str, Lef58CornerSpacing> {
+    tuple((
+        preceded(
+            ws(tag("CORNERSPACING")),
+            alt((
+                map(ws(tag("CONVEXCORNER")), |_| 0),
+                map(ws(tag("CONCAVECORNER")), |_| 1),
+            )),
+        ),
+        map(opt(ws(tag("SAMEMASK"))), |x| x.is_some()),
+        opt(preceded(ws(tag("CORNERONLY")), float)),
+        opt(preceded(ws(tag("EXCEPTEOL")), float)),
+        many1(tuple((
+            preceded(ws(tag("WIDTH")), float),
+            preceded(ws(tag("SPACING")), float),
+            opt(float),
+        ))),
+    ))(input)
+    .map(|(res, data)| {
+        (
+            res,
+            Lef58CornerSpacing {
+                corner_type: data.0,
+                same_mask: data.1,
+                corner_only: data.2,
+                except_eol: data.3,
+                spacing: data
+                    .4
+                    .into_iter()
+                    .map(|(w, h, v)| (w, h, v.unwrap_or(h)))
+                    .collect(),
+            },
+        )
+    })
+}
+
+// EDGETYPE {RIGHT | LEFT | TOP | BOTTOM} edgeType [CELLROW cellRow | HALFROW]
+//          [RANGE begin end]
+fn lef58_edgetype(input: &str) -> LefRes<&str, Lef58EdgeType> {
+    tuple((
+        preceded(ws(tag("EDGETYPE")), edge_encode),
+        tstring,
+        opt(preceded(ws(tag("CELLROW")), positive_number)),
+        map(opt(ws(tag("HALFROW"))), |x| x.is_some()),
+        opt(preceded(ws(tag("RANGE")), tuple((float, float)))),
+    ))(input)
+    .map(|(res, data)| {
+        (
+            res,
+            Lef58EdgeType {
+                edge: data.0,
+                edge_type: data.1.to_string(),
+                cell_row: data.2,
+                half_row: data.3,
+                range: data.4,
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_lef58_rules() {
+        let property = vec![
+            (
+                "LEF58_SPACING".to_string(),
+                PropValue::String(
+                    "SPACING 0.05 ENDOFLINE 0.06 WITHIN 0.025 ;
+                     SPACING 0.08 SAMEMASK ;
+                     SPACING 0.1 NOTCHLENGTH 0.2 ;"
+                        .to_string(),
+                ),
+            ),
+            (
+                "LEF58_CUTCLASS".to_string(),
+                PropValue::String(
+                    "CUTCLASS VSINGLECUT WIDTH 0.05 ; CUTCLASS VDOUBLECUT WIDTH 0.05 LENGTH 0.13 CUTS 2 ;"
+                        .to_string(),
+                ),
+            ),
+            (
+                "LEF58_CORNERSPACING".to_string(),
+                PropValue::String(
+                    "CORNERSPACING CONVEXCORNER EXCEPTEOL 0.08 WIDTH 0.0 SPACING 0.1 WIDTH 0.2 SPACING 0.2 0.15 ;"
+                        .to_string(),
+                ),
+            ),
+            (
+                "LEF58_EOLKEEPOUT".to_string(),
+                PropValue::String("EOLKEEPOUT 0.06 EXTENSION 0.02 0.03 0.08 CORNERONLY ;".to_string()),
+            ),
+            ("LEF58_TYPE".to_string(), PropValue::String("TYPE NWELL ;".to_string())),
+            (
+                "LEF58_EDGETYPE".to_string(),
+                PropValue::String("EDGETYPE LEFT gap CELLROW 2 ;".to_string()),
+            ),
+            (
+                "LEF58_AREA".to_string(),
+                PropValue::String("AREA 0.02 MASK 1 ;".to_string()),
+            ),
+            ("vendorRev".to_string(), PropValue::String("1.1".to_string())),
+        ];
+        let rules = lef58_rules(&property);
+        assert_eq!(rules.len(), 10);
+        match &rules[0] {
+            Lef58Rule::Spacing(x) => {
+                assert_eq!(x.min_spacing, 0.05);
+                assert_eq!(x.end_of_line.as_ref().unwrap().eol_width, 0.06);
+            }
+            _ => panic!(),
+        }
+        match &rules[1] {
+            Lef58Rule::Spacing(x) => assert!(x.same_mask),
+            _ => panic!(),
+        }
+        match &rules[2] {
+            Lef58Rule::Raw((name, text)) => {
+                assert_eq!(name, "LEF58_SPACING");
+                assert_eq!(text, "SPACING 0.1 NOTCHLENGTH 0.2");
+            }
+            _ => panic!(),
+        }
+        match &rules[4] {
+            Lef58Rule::CutClass(x) => {
+                assert_eq!(x.class_name, "VDOUBLECUT");
+                assert_eq!(x.num_cuts, Some(2));
+            }
+            _ => panic!(),
+        }
+        match &rules[5] {
+            Lef58Rule::CornerSpacing(x) => {
+                assert_eq!(x.except_eol, Some(0.08));
+                assert_eq!(x.spacing, vec![(0.0, 0.1, 0.1), (0.2, 0.2, 0.15)]);
+            }
+            _ => panic!(),
+        }
+        match &rules[6] {
+            Lef58Rule::EolKeepout(x) => assert_eq!(x.extension, (0.02, 0.03, 0.08)),
+            _ => panic!(),
+        }
+        match &rules[7] {
+            Lef58Rule::Type(x) => assert_eq!(x, "NWELL"),
+            _ => panic!(),
+        }
+        match &rules[8] {
+            Lef58Rule::EdgeType(x) => {
+                assert_eq!(x.edge, 1);
+                assert_eq!(x.cell_row, Some(2));
+            }
+            _ => panic!(),
+        }
+        match &rules[9] {
+            Lef58Rule::Raw((name, _)) => assert_eq!(name, "LEF58_AREA"),
+            _ => panic!(),
+        }
+    }
+}
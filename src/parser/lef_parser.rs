@@ -1,32 +1,1075 @@
-use super::base::ws;
+use super::base::{lef_comments, trailing_comment, ws};
 use super::header_parser::*;
+use super::layer_parser::*;
 use super::macro_parser::*;
+use super::nondefault_parser::*;
+use super::property_parser::*;
 use super::site_parser::*;
-use crate::{model::LefData, LefRes};
+use super::units_parser::*;
+use super::via_parser::*;
+use super::viarule_parser::*;
+use crate::{
+    model::{
+        LefData, LefLayer, LefMacro, LefSite, LefUnits, LefVia, LefViaRule, MacroObs,
+        NonDefaultRule, PortShape, PropValue, PropertyDefinition,
+    },
+    LefRes,
+};
+use nom::branch::alt;
 use nom::bytes::complete::tag;
+use nom::combinator::{cut, eof, map, opt, peek};
 
 use nom::error::context;
-use nom::multi::many1;
-use nom::sequence::terminated;
-use nom::sequence::tuple;
+use nom::multi::many0;
+use nom::sequence::{preceded, tuple};
+
+enum TechStatement {
+    Header(HeaderStatement),
+    Units(LefUnits),
+    ManufacturingGrid(f64),
+    PropertyDefinitions(Vec<PropertyDefinition>),
+    Property(Vec<(String, PropValue)>),
+    Layer(Box<LefLayer>),
+    Via(Box<LefVia>),
+    ViaRule(LefViaRule),
+    NonDefaultRule(NonDefaultRule),
+    Site(LefSite),
+    Macro(LefMacro),
+}
+
 pub fn lef_parser(input: &str) -> LefRes<&str, LefData> {
     context(
         "Cell LEF",
-        terminated(
-            tuple((header_section, site_parser, many1(macro_parser))),
-            tuple((ws(tag("END")), ws(tag("LIBRARY")))),
-        ),
+        tuple((
+            tuple((
+                lef_comments,
+                many0(tuple((
+                    lef_comments,
+                    alt((
+                        map(header_statement, TechStatement::Header),
+                        map(units_parser, TechStatement::Units),
+                        map(manufacturing_grid, TechStatement::ManufacturingGrid),
+                        map(
+                            property_definitions_parser,
+                            TechStatement::PropertyDefinitions,
+                        ),
+                        map(property_statement, TechStatement::Property),
+                        // once the keyword of an object is seen, its own error is the one to
+                        // report, not the END LIBRARY / end of file one below
+                        preceded(
+                            peek(ws(tag("LAYER"))),
+                            cut(map(layer_parser, |x| TechStatement::Layer(Box::new(x)))),
+                        ),
+                        preceded(
+                            peek(ws(tag("VIARULE"))),
+                            cut(map(viarule_parser, TechStatement::ViaRule)),
+                        ),
+                        preceded(
+                            peek(ws(tag("VIA"))),
+                            cut(map(via_parser, |x| TechStatement::Via(Box::new(x)))),
+                        ),
+                        preceded(
+                            peek(ws(tag("NONDEFAULTRULE"))),
+                            cut(map(nondefaultrule_parser, TechStatement::NonDefaultRule)),
+                        ),
+                        preceded(
+                            peek(ws(tag("SITE"))),
+                            cut(map(site_parser, TechStatement::Site)),
+                        ),
+                        preceded(
+                            peek(ws(tag("MACRO"))),
+                            cut(map(macro_parser, TechStatement::Macro)),
+                        ),
+                    )),
+                    trailing_comment,
+                ))),
+                lef_comments,
+            )),
+            // END LIBRARY is optional since 5.6. Only comments may follow the library
+            map(opt(tuple((ws(tag("END")), ws(tag("LIBRARY"))))), |x| {
+                x.is_some()
+            }),
+            lef_comments,
+            ws(eof),
+        )),
     )(input)
-    .map(|(res, data)| {
-        (
-            res,
-            LefData {
-                version: (data.0).1.map_or(5.7, |s| s),
-                dividechar: (data.0).2.map_or("/".to_string(), |s| s.to_string()),
-                busbitchar: (data.0).3.map_or("[]".to_string(), |s| s.to_string()),
-                site: data.1,
-                macro_: data.2,
-            },
-        )
+    .map(|(res, (data, end_library, end_comments, _))| {
+        let mut lef = LefData {
+            version: 5.7,
+            dividechar: "/".to_string(),
+            busbitchar: "[]".to_string(),
+            names_case_sensitive: None,
+            no_wire_extension_at_pin: None,
+            clearance_measure: None,
+            use_min_spacing: None,
+            max_via_stack: None,
+            fixed_mask: false,
+            antenna_input_gate_area: None,
+            antenna_inout_diff_area: None,
+            antenna_output_diff_area: None,
+            same_net_spacing: Vec::new(),
+            units: None,
+            manufacturing_grid: None,
+            property_definitions: Vec::new(),
+            property: Vec::new(),
+            layer: Vec::new(),
+            via: Vec::new(),
+            viarule: Vec::new(),
+            nondefaultrule: Vec::new(),
+            site: Vec::new(),
+            macro_: Vec::new(),
+            comments: data.0,
+            warnings: Vec::new(),
+        };
+        // comments go to the object they are in front of or end on the same line with,
+        // if it keeps any
+        for (comments, statement, trailing) in data.1 {
+            match statement {
+                TechStatement::Header(x) => {
+                    lef.comments.extend(comments);
+                    lef.comments.extend(trailing);
+                    header_fold(&mut lef, x)
+                }
+                TechStatement::Units(x) => {
+                    lef.comments.extend(comments);
+                    lef.comments.extend(trailing);
+                    lef.units = Some(x)
+                }
+                TechStatement::ManufacturingGrid(x) => {
+                    lef.comments.extend(comments);
+                    lef.comments.extend(trailing);
+                    lef.manufacturing_grid = Some(x)
+                }
+                TechStatement::PropertyDefinitions(x) => {
+                    lef.comments.extend(comments);
+                    lef.comments.extend(trailing);
+                    lef.property_definitions.extend(x)
+                }
+                TechStatement::Property(x) => {
+                    lef.comments.extend(comments);
+                    lef.comments.extend(trailing);
+                    lef.property.extend(x)
+                }
+                TechStatement::Layer(mut x) => {
+                    x.comments.splice(0..0, comments);
+                    x.comments.extend(trailing);
+                    for y in &x.unparsed {
+                        lef.warnings
+                            .push(format!("LAYER {}: skipped `{}`", x.layer_name, y));
+                    }
+                    lef.layer.push(*x)
+                }
+                TechStatement::Via(mut x) => {
+                    x.comments.splice(0..0, comments);
+                    x.comments.extend(trailing);
+                    lef.via.push(*x)
+                }
+                TechStatement::ViaRule(mut x) => {
+                    x.comments.splice(0..0, comments);
+                    x.comments.extend(trailing);
+                    lef.viarule.push(x)
+                }
+                TechStatement::NonDefaultRule(mut x) => {
+                    x.comments.splice(0..0, comments);
+                    x.comments.extend(trailing);
+                    lef.nondefaultrule.push(x)
+                }
+                TechStatement::Site(mut x) => {
+                    x.comments.splice(0..0, comments);
+                    x.comments.extend(trailing);
+                    lef.site.push(x)
+                }
+                TechStatement::Macro(mut x) => {
+                    x.comments.splice(0..0, comments);
+                    x.comments.extend(trailing);
+                    lef.macro_.push(x)
+                }
+            }
+        }
+        lef.comments.extend(data.2);
+        lef.comments.extend(end_comments);
+        check_version(&mut lef, end_library);
+        (res, lef)
     })
 }
+
+// statements and defaults that depend on the declared VERSION. The grammar accepts
+// every dialect from 5.3 to 5.8, anything that does not fit the version is a warning.
+// defaults only fill in statements the library leaves out.
+fn check_version(lef: &mut LefData, end_library: bool) {
+    let version = lef.version;
+    let mut newer: Vec<(f64, String)> = Vec::new(); // (version introducing it, construct)
+    let mut obsolete: Vec<(f64, String)> = Vec::new(); // (version removing it, construct)
+    if lef.max_via_stack.is_some() {
+        newer.push((5.5, "MAXVIASTACK".to_string()));
+    }
+    if lef.fixed_mask {
+        newer.push((5.8, "FIXEDMASK".to_string()));
+    }
+    if lef.antenna_input_gate_area.is_some()
+        || lef.antenna_inout_diff_area.is_some()
+        || lef.antenna_output_diff_area.is_some()
+    {
+        obsolete.push((
+            5.4,
+            "ANTENNAINPUTGATEAREA, ANTENNAINOUTDIFFAREA or ANTENNAOUTPUTDIFFAREA".to_string(),
+        ));
+    }
+    for x in &lef.layer {
+        if x.antenna.iter().any(|y| y.model != 0) {
+            newer.push((5.5, format!("ANTENNAMODEL in LAYER {}", x.layer_name)));
+        }
+        if x.antenna.iter().any(|y| {
+            y.cum_routing_plus_cut
+                || y.gate_plus_diff.is_some()
+                || y.area_minus_diff.is_some()
+                || y.area_diff_reduce_pwl.is_some()
+        }) {
+            newer.push((
+                5.7,
+                format!(
+                    "ANTENNACUMROUTINGPLUSCUT, ANTENNAGATEPLUSDIFF, ANTENNAAREAMINUSDIFF or \
+                     ANTENNAAREADIFFREDUCEPWL in LAYER {}",
+                    x.layer_name
+                ),
+            ));
+        }
+        if x.prl_spacing_table.is_some() {
+            newer.push((5.5, format!("SPACINGTABLE in LAYER {}", x.layer_name)));
+        }
+        if x.two_widths_spacing_table.is_some() {
+            newer.push((
+                5.7,
+                format!("SPACINGTABLE TWOWIDTHS in LAYER {}", x.layer_name),
+            ));
+        }
+    }
+    for x in &lef.nondefaultrule {
+        if x.hard_spacing || !x.min_cuts.is_empty() {
+            newer.push((
+                5.6,
+                format!("HARDSPACING or MINCUTS in NONDEFAULTRULE {}", x.rule_name),
+            ));
+        }
+    }
+    let shape_mask =
+        |x: &PortShape| x.mask.is_some() || x.geometries.iter().any(|y| y.mask().is_some());
+    for x in &lef.macro_ {
+        if x.fixed_mask {
+            newer.push((5.8, format!("FIXEDMASK in MACRO {}", x.macro_name)));
+        }
+        let pin_mask = x.macro_pin.iter().flat_map(|y| &y.pin_port).any(|y| {
+            y.port_shape.iter().any(shape_mask) || y.port_via.iter().any(|z| z.mask().is_some())
+        });
+        let obs_mask = x.macro_obs.iter().any(|y| match y {
+            MacroObs::LayerObj(z) => shape_mask(z),
+            MacroObs::ViaObj(z) => z.mask().is_some(),
+        });
+        if pin_mask || obs_mask {
+            newer.push((5.8, format!("MASK in MACRO {}", x.macro_name)));
+        }
+        for pin in &x.macro_pin {
+            if let Some(antenna) = &pin.pin_antenna {
+                if antenna.model.iter().any(|y| y.model != 0) {
+                    newer.push((
+                        5.5,
+                        format!(
+                            "ANTENNAMODEL in PIN {} of MACRO {}",
+                            pin.pin_name, x.macro_name
+                        ),
+                    ));
+                }
+                if !(antenna.size.is_empty()
+                    && antenna.metal_area.is_empty()
+                    && antenna.metal_length.is_empty())
+                {
+                    obsolete.push((
+                        5.4,
+                        format!(
+                            "ANTENNASIZE, ANTENNAMETALAREA or ANTENNAMETALLENGTH in PIN {} of MACRO {}",
+                            pin.pin_name, x.macro_name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    if version < 5.6 {
+        if lef.names_case_sensitive.is_none() {
+            lef.warnings
+                .push("NAMESCASESENSITIVE is required before VERSION 5.6".to_string());
+        }
+        if !end_library {
+            lef.warnings
+                .push("END LIBRARY is required before VERSION 5.6".to_string());
+        }
+    } else {
+        // names are always case sensitive since 5.6
+        if lef.names_case_sensitive.is_some() {
+            obsolete.push((5.6, "NAMESCASESENSITIVE".to_string()));
+        }
+        if lef.no_wire_extension_at_pin.is_some() {
+            obsolete.push((5.6, "NOWIREEXTENSIONATPIN".to_string()));
+        }
+        lef.names_case_sensitive.get_or_insert(true);
+    }
+    for (since, what) in newer.into_iter().filter(|x| version < x.0) {
+        lef.warnings.push(format!(
+            "{} needs VERSION {} or later, the library is VERSION {}",
+            what, since, version
+        ));
+    }
+    for (since, what) in obsolete.into_iter().filter(|x| version >= x.0) {
+        lef.warnings.push(format!(
+            "{} is obsolete since VERSION {}, the library is VERSION {}",
+            what, since, version
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Lef58Rule, PortGeometry};
+    #[test]
+    fn test_layer_stack() {
+        let test_str = "VERSION 5.7 ;
+LAYER poly
+  TYPE MASTERSLICE ;
+END poly
+LAYER contact
+  TYPE CUT ;
+END contact
+LAYER metal1
+  TYPE ROUTING ;
+  DIRECTION HORIZONTAL ;
+END metal1
+LAYER via1
+  TYPE CUT ;
+END via1
+LAYER metal2
+  TYPE ROUTING ;
+  DIRECTION VERTICAL ;
+END metal2
+LAYER via2
+  TYPE CUT ;
+END via2
+LAYER metal3
+  TYPE ROUTING ;
+END metal3
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        assert_eq!(lef.layer_index("metal1"), Some(2));
+        assert_eq!(
+            lef.routing_layer_above("metal1")
+                .map(|x| x.layer_name.as_str()),
+            Some("metal2")
+        );
+        assert!(lef.routing_layer_below("metal1").is_none());
+        assert_eq!(
+            lef.cut_layer_below("metal1").map(|x| x.layer_name.as_str()),
+            Some("contact")
+        );
+        assert_eq!(
+            lef.cut_between("metal3", "metal2")
+                .map(|x| x.layer_name.as_str()),
+            Some("via2")
+        );
+        assert!(lef.cut_between("metal1", "metal3").is_none());
+    }
+
+    #[test]
+    fn test_resolve_via() {
+        let test_str = "VERSION 5.7 ;
+VIA via12
+  LAYER metal1 ;
+    RECT -0.1 -0.05 0.1 0.05 ;
+  LAYER via1 ;
+    RECT -0.05 -0.05 0.05 0.05 ;
+END via12
+VIA via12_2x1
+  VIARULE M2_M1 ;
+  CUTSIZE 0.1 0.1 ;
+  LAYERS metal1 via1 metal2 ;
+  CUTSPACING 0.1 0.1 ;
+  ENCLOSURE 0.05 0 0 0.05 ;
+  ROWCOL 1 2 ;
+  OFFSET 0 0 0.1 0 ;
+END via12_2x1
+NONDEFAULTRULE wide
+  LAYER metal1
+    WIDTH 0.2 ;
+  END metal1
+  VIA via12_wide
+    LAYER metal1 ;
+      RECT -0.2 -0.1 0.2 0.1 ;
+  END via12_wide
+END wide
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        let shapes = lef
+            .resolve_via(&PortGeometry::Via(("via12".to_string(), (1.0, 2.0), None)))
+            .unwrap();
+        assert_eq!(shapes.len(), 2);
+        match shapes[0].geometries[0] {
+            PortGeometry::Rect((x, _)) => assert_eq!(x, ((0.9, 1.95), (1.1, 2.05))),
+            _ => panic!(),
+        }
+        assert!(lef
+            .resolve_via(&PortGeometry::Via(("via23".to_string(), (0.0, 0.0), None)))
+            .is_none());
+
+        // vias of a NONDEFAULTRULE are found too
+        let shapes = lef
+            .resolve_via(&PortGeometry::Via((
+                "via12_wide".to_string(),
+                (1.0, 0.0),
+                None,
+            )))
+            .unwrap();
+        match shapes[0].geometries[0] {
+            PortGeometry::Rect((x, _)) => assert_eq!(x, ((0.8, -0.1), (1.2, 0.1))),
+            _ => panic!(),
+        }
+
+        // a VIARULE via gets its shapes from the parameters, compared in nm
+        let nm = |x: f64| (x * 1000.0).round() as i64;
+        let via = lef.find_via("via12_2x1").unwrap();
+        assert_eq!(via.via_rule.as_ref().unwrap().rule_name, "M2_M1");
+        let rects: Vec<_> = via
+            .via_layer
+            .iter()
+            .map(|x| {
+                let rects: Vec<_> = x
+                    .geometries
+                    .iter()
+                    .map(|g| match g {
+                        PortGeometry::Rect((((x1, y1), (x2, y2)), _)) => {
+                            (nm(*x1), nm(*y1), nm(*x2), nm(*y2))
+                        }
+                        _ => panic!(),
+                    })
+                    .collect();
+                (x.layer_name.as_str(), rects)
+            })
+            .collect();
+        assert_eq!(
+            rects,
+            vec![
+                ("metal1", vec![(-200, -50, 200, 50)]),
+                ("via1", vec![(-150, -50, -50, 50), (50, -50, 150, 50)]),
+                ("metal2", vec![(-50, -100, 250, 100)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generate_via() {
+        let test_str = "VERSION 5.7 ;
+VIARULE M2_M1 GENERATE
+  LAYER metal1 ;
+    ENCLOSURE 0 0.035 ;
+  LAYER metal2 ;
+    ENCLOSURE 0 0.035 ;
+    WIDTH 0.07 TO 10 ;
+  LAYER via1 ;
+    RECT -0.035 -0.035 0.035 0.035 ;
+    SPACING 0.16 BY 0.16 ;
+END M2_M1
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        let via = lef
+            .generate_via(((0.0, 0.0), (0.5, 0.14)), "metal2", "metal1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(via.rule_name, "M2_M1");
+        assert_eq!(via.cut_array, (3, 1));
+        assert_eq!(via.via_layer[2].layer_name, "via1");
+        assert_eq!(via.via_layer[2].geometries.len(), 3);
+        assert!(lef
+            .generate_via(((0.0, 0.0), (0.05, 0.05)), "metal1", "metal2")
+            .unwrap()
+            .is_none());
+        assert!(lef
+            .generate_via(((0.0, 0.0), (0.5, 0.5)), "metal2", "metal3")
+            .unwrap()
+            .is_none());
+        // more cuts than a u32 counts, on one side or in total
+        assert!(lef
+            .generate_via(((0.0, 0.0), (5.0, 1e12)), "metal1", "metal2")
+            .is_err());
+        assert!(lef
+            .generate_via(((0.0, 0.0), (5.0, 1e8)), "metal1", "metal2")
+            .is_err());
+
+        // a narrow vertical wire crossing a wide horizontal rail
+        let test_str = "VERSION 5.5 ;
+NAMESCASESENSITIVE ON ;
+VIARULE RAIL GENERATE
+  LAYER metal1 ;
+    DIRECTION HORIZONTAL ;
+    WIDTH 1.0 TO 10 ;
+  LAYER metal2 ;
+    DIRECTION VERTICAL ;
+    WIDTH 0.1 TO 0.5 ;
+  LAYER via1 ;
+    RECT -0.035 -0.035 0.035 0.035 ;
+    SPACING 0.16 BY 0.16 ;
+END RAIL
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        let via = lef
+            .generate_via(((0.0, 0.0), (0.2, 2.0)), "metal1", "metal2")
+            .unwrap()
+            .unwrap();
+        assert_eq!(via.cut_array, (1, 13));
+        // the rail itself is narrow now
+        assert!(lef
+            .generate_via(((0.0, 0.0), (0.2, 0.2)), "metal1", "metal2")
+            .unwrap()
+            .is_none());
+        // or the crossing wire is too wide
+        assert!(lef
+            .generate_via(((0.0, 0.0), (2.0, 2.0)), "metal1", "metal2")
+            .unwrap()
+            .is_none());
+        // empty overlaps and arrays too large to count are errors
+        assert!(lef
+            .generate_via(((0.0, 0.0), (0.0, 2.0)), "metal1", "metal2")
+            .is_err());
+        assert!(lef
+            .generate_via(((0.0, 0.0), (0.2, -2.0)), "metal1", "metal2")
+            .is_err());
+    }
+
+    #[test]
+    fn test_taper_rule() {
+        let test_str = "VERSION 5.7 ;
+NONDEFAULTRULE clk_2w
+  LAYER metal1
+    WIDTH 0.14 ;
+  END metal1
+END clk_2w
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+MACRO CKBUF
+  CLASS CORE ;
+  ORIGIN 0 0 ;
+  FOREIGN CKBUF 0 0 ;
+  SIZE 1.0 BY 1.4 ;
+  SYMMETRY X Y ;
+  SITE core ;
+  PIN Z
+    TAPERRULE clk_2w ;
+    DIRECTION OUTPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0.1 0.1 0.2 0.2 ;
+    END
+  END Z
+  PIN A
+    TAPERRULE DEFAULT ;
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0.3 0.1 0.4 0.2 ;
+    END
+  END A
+END CKBUF
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        let pins = &lef.macro_[0].macro_pin;
+        assert_eq!(
+            lef.taper_rule(&pins[0]).map(|x| x.rule_name.as_str()),
+            Some("clk_2w")
+        );
+        assert!(lef.taper_rule(&pins[1]).is_none());
+    }
+
+    #[test]
+    fn test_units_conversion() {
+        let test_str = "VERSION 5.7 ;
+UNITS
+  DATABASE MICRONS 2000 ;
+END UNITS
+MANUFACTURINGGRID 0.005 ;
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        assert_eq!(lef.dbu_per_micron(), 2000);
+        assert_eq!(lef.to_dbu(1.2345), 2469);
+        assert_eq!(lef.to_micron(2469), 1.2345);
+        assert_eq!(lef.snap_to_grid(1.2345), 1.235);
+        assert_eq!(lef.snap_to_grid(-0.0121), -0.01);
+
+        // without UNITS the grid does not divide the default 100 DBU evenly
+        let lef = lef_parser("VERSION 5.7 ;\nMANUFACTURINGGRID 0.005 ;\n")
+            .unwrap()
+            .1;
+        assert_eq!(lef.dbu_per_micron(), 100);
+        assert_eq!(lef.snap_to_grid(0.013), 0.015);
+        assert_eq!(lef.snap_to_grid(0.0126), 0.015);
+        // nor is a grid finer than 1 DBU coarsened
+        let lef = lef_parser("VERSION 5.7 ;\nMANUFACTURINGGRID 0.001 ;\n")
+            .unwrap()
+            .1;
+        assert!((lef.snap_to_grid(1.2346) - 1.235).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_multiple_sites() {
+        let test_str = "VERSION 5.8 ;
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+MACRO INV
+  CLASS CORE ;
+  ORIGIN 0 0 ;
+  FOREIGN INV 0 0 ;
+  SIZE 0.4 BY 1.4 ;
+  SYMMETRY X Y ;
+  SITE core ;
+  PIN A
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0.05 0.2 0.15 0.6 ;
+    END
+  END A
+END INV
+LAYER metal1
+  TYPE ROUTING ;
+END metal1
+SITE dh_core
+  CLASS CORE ;
+  SIZE 0.2 BY 2.8 ;
+END dh_core
+SITE io
+  CLASS PAD ;
+  SIZE 1 BY 120 ;
+END io
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        assert_eq!(lef.site.len(), 3);
+        assert_eq!(lef.layer.len(), 1);
+        assert_eq!(lef.find_site("dh_core").unwrap().site_size, (0.2, 2.8));
+        assert!(lef.find_site("io").unwrap().site_class);
+        let inv = lef.find_macro("INV").unwrap();
+        assert!(lef.find_site(&inv.macro_site[0].site_name).is_some());
+    }
+
+    #[test]
+    fn test_bus_bits() {
+        let test_str = "VERSION 5.8 ;
+BUSBITCHARS \"<>\" ;
+DIVIDERCHAR \"|\" ;
+MACRO REG
+  CLASS CORE ;
+  PIN A<1>
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0 0 0.1 0.1 ;
+    END
+  END A<1>
+  PIN A<0>
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0 0 0.1 0.1 ;
+    END
+  END A<0>
+  PIN CLK
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0 0 0.1 0.1 ;
+    END
+  END CLK
+  PIN A<3>
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0 0 0.1 0.1 ;
+    END
+  END A<3>
+  PIN B<7>
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0 0 0.1 0.1 ;
+    END
+  END B<7>
+  PIN A<2>
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0 0 0.1 0.1 ;
+    END
+  END A<2>
+END REG
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        assert_eq!(lef.busbitchar, "<>");
+        assert_eq!(lef.dividechar, "|");
+        assert_eq!(lef.split_bus_bit("data<12>"), Some(("data", 12)));
+        assert_eq!(lef.split_bus_bit("data[12]"), None);
+        assert_eq!(lef.split_bus_bit("data\\<12>"), None);
+        assert_eq!(
+            lef.split_hierarchy("u1|u2\\|x|A<0>"),
+            vec!["u1", "u2\\|x", "A<0>"]
+        );
+        let buses = lef.pin_buses(lef.find_macro("REG").unwrap());
+        assert_eq!(buses.len(), 2);
+        assert_eq!(buses[0].bus_name, "A");
+        assert_eq!((buses[0].msb, buses[0].lsb), (3, 0));
+        assert_eq!(buses[0].bit, vec![(1, 0), (0, 1), (3, 3), (2, 5)]);
+        assert_eq!((buses[1].msb, buses[1].lsb), (7, 7));
+    }
+
+    #[test]
+    fn test_comments() {
+        let test_str = "# generated by a library tool
+# no VERSION in this file
+UNITS # units
+  DATABASE MICRONS 2000 ; # dbu
+END UNITS
+# first metal
+LAYER metal1 # inline
+  TYPE ROUTING ; # routing
+  # a comment line inside LAYER
+  WIDTH 0.1 ;
+END metal1
+SITE core
+  CLASS CORE ; SIZE 0.2 BY 1.4 ;
+END core
+MACRO INV # inverter
+  # core
+  CLASS CORE ;
+  # inside macro
+  SIZE 0.4 BY 1.4 ;
+  # input pin
+  PIN A
+    DIRECTION INPUT ; # in
+    PORT
+      LAYER metal1 ; # shapes
+        RECT 0 0 0.1 0.1 ; # rect
+    END
+    # before END A
+  END A # end A
+  PIN B[0]
+    DIRECTION OUTPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0.2 0 0.3 0.1 ;
+    END
+  END B[0]
+  OBS # no obstruction layers but a via
+    VIA 0 0 via1 ;
+  END
+END INV # end INV
+# trailing
+END LIBRARY";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        assert_eq!(lef.dbu_per_micron(), 2000);
+        assert_eq!(
+            lef.comments,
+            vec![
+                "generated by a library tool",
+                "no VERSION in this file",
+                "trailing"
+            ]
+        );
+        assert_eq!(lef.layer[0].comments, vec!["first metal"]);
+        assert_eq!(lef.layer[0].width, Some(0.1));
+        let inv = lef.find_macro("INV").unwrap();
+        assert_eq!(
+            inv.comments,
+            vec!["inverter", "core", "inside macro", "end INV"]
+        );
+        assert_eq!(
+            inv.macro_pin[0].comments,
+            vec!["input pin", "in", "before END A", "end A"]
+        );
+        assert!(inv.macro_pin[1].comments.is_empty());
+        assert_eq!(inv.macro_obs.len(), 1);
+    }
+
+    #[test]
+    fn test_inner_error() {
+        let test_str = "VERSION 5.8 ;
+MACRO INV
+  CLASS CORE ;
+  PIN A
+    DIRECTION SIDEWAYS ;
+  END A
+END INV
+END LIBRARY";
+        let e = match lef_parser(test_str) {
+            Err(nom::Err::Failure(e)) => nom::error::convert_error(test_str, e),
+            _ => panic!("the bad DIRECTION is not a parse failure"),
+        };
+        assert!(e.starts_with("0: at line 5, in Tag:"));
+        assert!(e.contains("in Macro Pin Statement"));
+        assert!(!e.contains("Eof"));
+    }
+
+    #[test]
+    fn test_end_of_file() {
+        let test_str = "VERSION 5.8 ;
+SITE core
+  CLASS CORE ;
+  SIZE 0.2 BY 1.4 ;
+END core
+";
+        let (_, lef) = lef_parser(&format!("{}END LIBRARY\n# done\n", test_str)).unwrap();
+        assert_eq!(lef.comments, vec!["done"]);
+        // anything else after the library is an error at the unconsumed text
+        for tail in ["END LIBRARY\nSITE", "END LIBRARY ;", "FOO", "END"] {
+            let text = format!("{}{}", test_str, tail);
+            let e = match lef_parser(&text) {
+                Err(nom::Err::Error(e)) => nom::error::convert_error(text.as_str(), e),
+                _ => panic!("`{}` after the library is accepted", tail),
+            };
+            let line = tail.lines().last().unwrap();
+            assert!(e.starts_with("0: at line"));
+            assert!(e.contains(&format!("in Eof:\n{}\n", line)), "{}", e);
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        let test_str = "DIVIDERCHAR \"/\" ;
+NAMESCASESENSITIVE ON ;
+BUSBITCHARS \"[]\" ;
+VERSION 5.6 ;
+NOWIREEXTENSIONATPIN OFF ;
+CLEARANCEMEASURE EUCLIDEAN ;
+USEMINSPACING OBS OFF ;
+FIXEDMASK ;
+LAYER metal1
+  TYPE ROUTING ;
+END metal1
+LAYER metal2
+  TYPE ROUTING ;
+END metal2
+MAXVIASTACK 4 RANGE metal1 metal2 ;
+";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        assert_eq!(lef.version, 5.6);
+        assert_eq!(lef.dividechar, "/");
+        assert_eq!(lef.busbitchar, "[]");
+        assert_eq!(lef.names_case_sensitive, Some(true));
+        assert_eq!(lef.no_wire_extension_at_pin, Some(false));
+        assert_eq!(lef.clearance_measure, Some(1));
+        assert_eq!(lef.use_min_spacing, Some(false));
+        assert!(lef.fixed_mask);
+        assert_eq!(
+            lef.max_via_stack,
+            Some((4, Some(("metal1".to_string(), "metal2".to_string()))))
+        );
+        assert_eq!(lef.layer.len(), 2);
+
+        // without END LIBRARY a broken statement is still an error
+        assert!(
+            lef_parser(&test_str.replace("TYPE ROUTING ;\nEND metal2", "TYPE ;\nEND metal2"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        let test_str = "VERSION 5.5 ;
+BUSBITCHARS \"[]\" ;
+FIXEDMASK ;
+LAYER metal1
+  TYPE ROUTING ;
+  ANTENNACUMROUTINGPLUSCUT ;
+END metal1
+MACRO INV
+  CLASS CORE ;
+  PIN A
+    DIRECTION INPUT ;
+    ANTENNASIZE 0.5 LAYER metal1 ;
+    ANTENNAMODEL OXIDE2 ;
+    ANTENNAGATEAREA 0.1 ;
+    PORT
+      LAYER metal1 ;
+        RECT 0 0 0.1 0.1 ;
+    END
+  END A
+END INV
+";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        assert_eq!(lef.names_case_sensitive, None);
+        let antenna = lef.macro_[0].macro_pin[0].pin_antenna.as_ref().unwrap();
+        assert_eq!(antenna.size, vec![(0.5, Some("metal1".to_string()))]);
+        assert_eq!(
+            lef.warnings,
+            vec![
+                "NAMESCASESENSITIVE is required before VERSION 5.6",
+                "END LIBRARY is required before VERSION 5.6",
+                "FIXEDMASK needs VERSION 5.8 or later, the library is VERSION 5.5",
+                "ANTENNACUMROUTINGPLUSCUT, ANTENNAGATEPLUSDIFF, ANTENNAAREAMINUSDIFF or \
+                 ANTENNAAREADIFFREDUCEPWL in LAYER metal1 needs VERSION 5.7 or later, \
+                 the library is VERSION 5.5",
+                "ANTENNASIZE, ANTENNAMETALAREA or ANTENNAMETALLENGTH in PIN A of MACRO INV \
+                 is obsolete since VERSION 5.4, the library is VERSION 5.5",
+            ]
+        );
+
+        // the same library declared as 5.8, names are case sensitive by default
+        let (_, lef) = lef_parser(&test_str.replace("VERSION 5.5", "VERSION 5.8")).unwrap();
+        assert_eq!(lef.names_case_sensitive, Some(true));
+        assert_eq!(lef.warnings.len(), 1);
+
+        // 5.3 style pin antenna sizes without warnings
+        let test_str = test_str
+            .replace("VERSION 5.5 ;", "VERSION 5.3 ;\nNAMESCASESENSITIVE ON ;")
+            .replace("FIXEDMASK ;\n", "")
+            .replace("  ANTENNACUMROUTINGPLUSCUT ;\n", "")
+            .replace("    ANTENNAMODEL OXIDE2 ;\n", "")
+            + "END LIBRARY";
+        let (_, lef) = lef_parser(&test_str).unwrap();
+        assert!(lef.warnings.is_empty());
+
+        // an explicit value is not replaced by the default of the version
+        let (_, lef) = lef_parser("VERSION 5.6 ;\nNAMESCASESENSITIVE OFF ;\n").unwrap();
+        assert_eq!(lef.names_case_sensitive, Some(false));
+        assert_eq!(
+            lef.warnings,
+            vec!["NAMESCASESENSITIVE is obsolete since VERSION 5.6, the library is VERSION 5.6"]
+        );
+    }
+
+    #[test]
+    fn test_version_5_3() {
+        let test_str = "VERSION 5.3 ;
+NAMESCASESENSITIVE ON ;
+BUSBITCHARS \"<>\" ;
+ANTENNAINPUTGATEAREA 0.25 ;
+ANTENNAINOUTDIFFAREA 0.5 ;
+ANTENNAOUTPUTDIFFAREA 1.0 ;
+LAYER metal1
+  TYPE ROUTING ;
+  WIDTH 0.2 ;
+END metal1
+LAYER via1
+  TYPE CUT ;
+END via1
+LAYER metal2
+  TYPE ROUTING ;
+  WIDTH 0.2 ;
+END metal2
+SPACING
+  SAMENET metal1 metal1 0.2 ;
+  SAMENET via1 via1 0.3 STACK ;
+END SPACING
+MACRO BUF
+  CLASS CORE ;
+  SIZE 0.6 BY 1.4 ;
+  PIN Z
+    DIRECTION OUTPUT ;
+    ANTENNASIZE 1.0 ;
+    PORT
+      LAYER metal1 ;
+        RECT 0.4 0 0.5 0.1 ;
+    END
+  END Z
+END BUF
+END LIBRARY
+";
+        let (_, lef) = lef_parser(test_str).unwrap();
+        assert!(lef.warnings.is_empty());
+        assert_eq!(lef.names_case_sensitive, Some(true));
+        assert_eq!(lef.antenna_input_gate_area, Some(0.25));
+        assert_eq!(lef.antenna_inout_diff_area, Some(0.5));
+        assert_eq!(lef.antenna_output_diff_area, Some(1.0));
+        assert_eq!(
+            lef.same_net_spacing,
+            vec![
+                ("metal1".to_string(), "metal1".to_string(), 0.2, false),
+                ("via1".to_string(), "via1".to_string(), 0.3, true),
+            ]
+        );
+        assert_eq!(lef.layer.len(), 3);
+
+        let (_, lef) = lef_parser(&test_str.replace("VERSION 5.3", "VERSION 5.5")).unwrap();
+        assert_eq!(
+            lef.warnings[0],
+            "ANTENNAINPUTGATEAREA, ANTENNAINOUTDIFFAREA or ANTENNAOUTPUTDIFFAREA \
+             is obsolete since VERSION 5.4, the library is VERSION 5.5"
+        );
+    }
+
+    #[test]
+    fn test_lef58_properties() {
+        let test_str = "VERSION 5.8 ;
+PROPERTYDEFINITIONS
+  LAYER LEF58_SPACING STRING ;
+  LAYER LEF58_CUTCLASS STRING ;
+  MACRO LEF58_EDGETYPE STRING ;
+END PROPERTYDEFINITIONS
+LAYER metal1
+  TYPE ROUTING ;
+  PROPERTY LEF58_SPACING \"SPACING 0.05 ENDOFLINE 0.06 WITHIN 0.025 ;
+    SPACING 0.1 NOTCHLENGTH 0.2 ;\" ;
+END metal1
+LAYER via1
+  TYPE CUT ;
+  PROPERTY LEF58_CUTCLASS \"CUTCLASS VSINGLECUT WIDTH 0.05 ;\" ;
+END via1
+MACRO FILL
+  CLASS CORE SPACER ;
+  SIZE 0.2 BY 1.4 ;
+  PROPERTY LEF58_EDGETYPE \"EDGETYPE LEFT gap CELLROW 2 ;\" ;
+END FILL
+END LIBRARY";
+        let lef: LefData = test_str.parse().unwrap();
+        let metal1 = lef.find_layer("metal1").unwrap();
+        assert_eq!(metal1.lef58_rule.len(), 2);
+        match &metal1.lef58_rule[0] {
+            Lef58Rule::Spacing(x) => {
+                assert_eq!(x.min_spacing, 0.05);
+                assert_eq!(x.end_of_line.as_ref().unwrap().eol_within, 0.025);
+            }
+            _ => panic!(),
+        }
+        assert!(
+            matches!(&metal1.lef58_rule[1], Lef58Rule::Raw((name, _)) if name == "LEF58_SPACING")
+        );
+        match &lef.find_layer("via1").unwrap().lef58_rule[0] {
+            Lef58Rule::CutClass(x) => {
+                assert_eq!(x.class_name, "VSINGLECUT");
+                assert_eq!(x.width, 0.05);
+            }
+            _ => panic!(),
+        }
+        match &lef.find_macro("FILL").unwrap().lef58_rule[0] {
+            Lef58Rule::EdgeType(x) => {
+                assert_eq!(x.edge, 1);
+                assert_eq!(x.edge_type, "gap");
+                assert_eq!(x.cell_row, Some(2));
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -1,20 +1,44 @@
 use crate::{
-    model::{LefMacro, MacroDensity, MacroPin, MacroSite, PortGeometry, PortShape},
+    model::{
+        ForeignCell, LefMacro, MacroDensity, MacroObs, MacroPin, MacroPort, MacroSite,
+        PortGeometry, PortShape, PropValue, StepPattern,
+    },
     LefRes,
 };
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until};
-use nom::combinator::{map, opt, value};
+use nom::bytes::complete::tag;
+use nom::combinator::{cut, map, map_opt, opt, peek};
 use nom::error::context;
-use nom::multi::many1;
-use nom::sequence::{delimited, preceded, separated_pair, tuple};
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, preceded, separated_pair, terminated, tuple};
 
-use super::base::{float, positive_number, qstring, tstring, ws};
-use super::common::{pt, pt_list, rect};
+use super::antenna_parser::{pin_antenna_fold, pin_antenna_statement, PinAntennaStatement};
+use super::base::{float, lef_comments, quoted, trailing_comment, tstring, ws};
+use super::common::{mask, pt, pt_list, rect, step_pattern, via_mask};
 use super::encoder::{
     macro_class_encode, macro_pin_direction_encode, macro_pin_port_class_encode,
-    macro_pin_shape_encode, orient_encode, use_type_encode,
+    macro_pin_shape_encode, macro_source_encode, orient_encode, use_type_encode,
 };
+use super::lef58_parser::lef58_rules;
+use super::property_parser::property_statement;
+enum MacroStatement {
+    Class(u8),
+    FixedMask,
+    Foreign(ForeignCell),
+    Origin((f64, f64)),
+    Eeq(String),
+    Leq(String),
+    Source(u8),
+    Power(f64),
+    Size((f64, f64)),
+    Symmetry(Vec<u8>),
+    Site(MacroSite),
+    Pin(Box<MacroPin>),
+    Obs(Vec<MacroObs>),
+    Density(Vec<MacroDensity>),
+    Property(Vec<(String, PropValue)>),
+}
+
 pub fn macro_parser(input: &str) -> LefRes<&str, LefMacro> {
     context(
         "Macro Statement",
@@ -22,60 +46,140 @@ pub fn macro_parser(input: &str) -> LefRes<&str, LefMacro> {
             ws(tag("MACRO")),
             tuple((
                 tstring,
-                delimited(ws(tag("CLASS")), macro_class_encode, ws(tag(";"))),
-                delimited(ws(tag("ORIGIN")), pt, ws(tag(";"))),
-                delimited(
-                    ws(tag("FOREIGN")),
-                    tuple((tstring, opt(pt), opt(orient_encode))),
-                    ws(tag(";")),
-                ),
-                delimited(
-                    ws(tag("SIZE")),
-                    separated_pair(float, ws(tag("BY")), float),
-                    ws(tag(";")),
-                ),
-                delimited(
-                    ws(tag("SYMMETRY")),
-                    many1(alt((
-                        map(ws(tag("X")), |_| 0),
-                        map(ws(tag("Y")), |_| 1),
-                        map(ws(tag("R90")), |_| 2),
-                    ))),
-                    ws(tag(";")),
-                ),
-                many1(site_statement),
-                many1(pin_statement),
-                opt(obs_statement),
-                opt(density_statement),
-                opt(delimited(ws(tag("EEQ")), tstring, ws(tag(";")))),
+                trailing_comment,
+                many0(tuple((lef_comments, macro_statement, trailing_comment))),
+                lef_comments,
             )),
             tuple((ws(tag("END")), tstring)),
         ),
     )(input)
     .map(|(res, data)| {
-        (
-            res,
-            LefMacro {
-                macro_name: data.0.to_string(),
-                macro_class: data.1,
-                foreign_cell: ((data.3).0.to_string(), (data.3).1, (data.3).2),
-                origin: data.2,
-                eeq_macro: data.10.map(|s| s.to_string()),
-                macro_size: data.4,
-                macro_symmetry: data.5,
-                macro_site: data.6,
-                macro_pin: data.7,
-                macro_obs: data.8,
-                macro_density: data.9,
-            },
-        )
+        let mut macro_ = LefMacro {
+            macro_name: data.0.to_string(),
+            macro_class: None,
+            fixed_mask: false,
+            foreign_cell: Vec::new(),
+            origin: (0.0, 0.0),
+            eeq_macro: None,
+            leq_macro: None,
+            source: None,
+            power: None,
+            macro_size: None,
+            macro_symmetry: Vec::new(),
+            macro_site: Vec::new(),
+            macro_pin: Vec::new(),
+            macro_obs: Vec::new(),
+            macro_density: Vec::new(),
+            property: Vec::new(),
+            lef58_rule: Vec::new(),
+            source_file: None,
+            comments: data.1.into_iter().collect(),
+        };
+        // comments around a PIN belong to the pin, the others to the macro
+        for (mut comments, mut statement, trailing) in data.2 {
+            comments.extend(trailing);
+            if let MacroStatement::Pin(x) = &mut statement {
+                x.comments.splice(0..0, comments.drain(..));
+            }
+            macro_.comments.extend(comments);
+            match statement {
+                MacroStatement::Class(x) => macro_.macro_class = Some(x),
+                MacroStatement::FixedMask => macro_.fixed_mask = true,
+                MacroStatement::Foreign(x) => macro_.foreign_cell.push(x),
+                MacroStatement::Origin(x) => macro_.origin = x,
+                MacroStatement::Eeq(x) => macro_.eeq_macro = Some(x),
+                MacroStatement::Leq(x) => macro_.leq_macro = Some(x),
+                MacroStatement::Source(x) => macro_.source = Some(x),
+                MacroStatement::Power(x) => macro_.power = Some(x),
+                MacroStatement::Size(x) => macro_.macro_size = Some(x),
+                MacroStatement::Symmetry(x) => macro_.macro_symmetry = x,
+                MacroStatement::Site(x) => macro_.macro_site.push(x),
+                MacroStatement::Pin(x) => macro_.macro_pin.push(*x),
+                MacroStatement::Obs(x) => macro_.macro_obs.extend(x),
+                MacroStatement::Density(x) => macro_.macro_density.extend(x),
+                MacroStatement::Property(x) => macro_.property.extend(x),
+            }
+        }
+        macro_.comments.extend(data.3);
+        macro_.lef58_rule = lef58_rules(&macro_.property);
+        (res, macro_)
     })
 }
 
+fn macro_statement(input: &str) -> LefRes<&str, MacroStatement> {
+    alt((
+        map(
+            delimited(ws(tag("CLASS")), macro_class_encode, ws(tag(";"))),
+            MacroStatement::Class,
+        ),
+        map(tuple((ws(tag("FIXEDMASK")), ws(tag(";")))), |_| {
+            MacroStatement::FixedMask
+        }),
+        map(
+            delimited(
+                ws(tag("FOREIGN")),
+                tuple((tstring, opt(pt), opt(orient_encode))),
+                ws(tag(";")),
+            ),
+            |x| MacroStatement::Foreign((x.0.to_string(), x.1, x.2)),
+        ),
+        map(
+            delimited(ws(tag("ORIGIN")), pt, ws(tag(";"))),
+            MacroStatement::Origin,
+        ),
+        map(delimited(ws(tag("EEQ")), tstring, ws(tag(";"))), |x| {
+            MacroStatement::Eeq(x.to_string())
+        }),
+        map(delimited(ws(tag("LEQ")), tstring, ws(tag(";"))), |x| {
+            MacroStatement::Leq(x.to_string())
+        }),
+        map(
+            delimited(ws(tag("SOURCE")), macro_source_encode, ws(tag(";"))),
+            MacroStatement::Source,
+        ),
+        map(
+            delimited(ws(tag("POWER")), float, ws(tag(";"))),
+            MacroStatement::Power,
+        ),
+        map(
+            delimited(
+                ws(tag("SIZE")),
+                separated_pair(float, ws(tag("BY")), float),
+                ws(tag(";")),
+            ),
+            MacroStatement::Size,
+        ),
+        map(
+            delimited(
+                ws(tag("SYMMETRY")),
+                many1(alt((
+                    map(ws(tag("X")), |_| 0),
+                    map(ws(tag("Y")), |_| 1),
+                    map(ws(tag("R90")), |_| 2),
+                ))),
+                ws(tag(";")),
+            ),
+            MacroStatement::Symmetry,
+        ),
+        map(site_statement, MacroStatement::Site),
+        // report the error inside a PIN or OBS rather than the missing END of the macro
+        preceded(
+            peek(ws(tag("PIN"))),
+            cut(map(pin_statement, |x| MacroStatement::Pin(Box::new(x)))),
+        ),
+        preceded(
+            peek(ws(tag("OBS"))),
+            cut(map(obs_statement, MacroStatement::Obs)),
+        ),
+        map(density_statement, MacroStatement::Density),
+        map(property_statement, MacroStatement::Property),
+    ))(input)
+}
+
 pub fn site_statement(input: &str) -> LefRes<&str, MacroSite> {
     delimited(
         ws(tag("SITE")),
-        tuple((tstring, opt(tstring))),
+        tuple((tstring, opt(tuple((pt, orient_encode, opt(step_pattern)))))),
         ws(tag(";")),
     )(input)
     .map(|(res, data)| {
@@ -83,82 +187,136 @@ pub fn site_statement(input: &str) -> LefRes<&str, MacroSite> {
             res,
             MacroSite {
                 site_name: data.0.to_string(),
-                site_pattern: data.1.map(|s| s.to_string()),
+                site_pattern: data.1,
             },
         )
     })
 }
 
-// antenna statement not support
+enum PinStatement {
+    TaperRule(String),
+    Direction(u8),
+    Use(u8),
+    NetExpr(String),
+    SupplySensitivity(String),
+    GroundSensitivity(String),
+    Shape(u8),
+    MustJoin(String),
+    Antenna(PinAntennaStatement),
+    Property(Vec<(String, PropValue)>),
+    Port(MacroPort),
+}
+
+// statements may come in any order. DIRECTION defaults to INPUT. Antenna values
+// belong to the ANTENNAMODEL in front of them, OXIDE1 before the first one.
 pub fn pin_statement(input: &str) -> LefRes<&str, MacroPin> {
     context(
         "Macro Pin Statement",
-        delimited(
-            ws(tag("PIN")),
-            tuple((
-                // basic infomation, must declare
-                tstring, // pin name
-                // 1
-                opt(delimited(ws(tag("TAPERRULE")), tstring, ws(tag(";")))),
-                // 2
-                delimited(
-                    ws(tag("DIRECTION")),
-                    macro_pin_direction_encode,
-                    ws(tag(";")),
-                ),
-                // 3
-                opt(delimited(ws(tag("USE")), use_type_encode, ws(tag(";")))),
-                // 4
-                opt(delimited(ws(tag("NETEXPR")), qstring, ws(tag(";")))),
-                // 5
-                opt(delimited(
-                    ws(tag("SUPPLYSENSITIVITY")),
-                    tstring,
-                    ws(tag(";")),
-                )),
-                // 6
-                opt(delimited(
-                    ws(tag("GROUNDSENSITIVITY")),
+        tuple((
+            delimited(
+                ws(tag("PIN")),
+                tuple((
                     tstring,
-                    ws(tag(";")),
+                    trailing_comment,
+                    many0(tuple((lef_comments, pin_body_statement, trailing_comment))),
+                    lef_comments,
                 )),
-                // 7
-                opt(delimited(
-                    ws(tag("SHAPE")),
-                    macro_pin_shape_encode,
-                    ws(tag(";")),
-                )),
-                // 8
-                opt(delimited(ws(tag("MUSTJOIN")), tstring, ws(tag(";")))),
-                // 9
-                opt(pin_antenna_statement),
-                // 10
-                macro_pin_port,
-            )),
-            tuple((ws(tag("END")), tstring)),
-        ),
+                tuple((ws(tag("END")), tstring)),
+            ),
+            trailing_comment,
+        )),
     )(input)
-    .map(|(res, data)| {
-        (
-            res,
-            MacroPin {
-                pin_name: data.0.to_string(),
-                direction: data.2,
-                pin_port: (data.10).1, // (class,MacroPortObj)
-                use_type: data.3.map_or(0, |s| s),
-                shape: data.7,
-                taper_rule: data.1.map(|x| x.to_string()),
-                net_expr: data.4.map(|x| x.to_string()),
-                supply_sensitivity: data.5.map(|x| x.to_string()),
-                ground_sensitivity: data.6.map(|x| x.to_string()),
-                mustjoin: data.8.map(|x| x.to_string()),
-                // pin_antenna: data.9,
-            },
-        )
+    .map(|(res, (data, end_comment))| {
+        let mut pin = MacroPin {
+            pin_name: data.0.to_string(),
+            taper_rule: None,
+            direction: 0,
+            use_type: 0,
+            net_expr: None,
+            ground_sensitivity: None,
+            supply_sensitivity: None,
+            mustjoin: None,
+            shape: None,
+            property: Vec::new(),
+            pin_port: Vec::new(),
+            pin_antenna: None,
+            comments: data.1.into_iter().collect(),
+        };
+        let mut antenna_model = 0;
+        for (comments, statement, trailing) in data.2 {
+            pin.comments.extend(comments);
+            pin.comments.extend(trailing);
+            match statement {
+                PinStatement::TaperRule(x) => pin.taper_rule = Some(x),
+                PinStatement::Direction(x) => pin.direction = x,
+                PinStatement::Use(x) => pin.use_type = x,
+                PinStatement::NetExpr(x) => pin.net_expr = Some(x),
+                PinStatement::SupplySensitivity(x) => pin.supply_sensitivity = Some(x),
+                PinStatement::GroundSensitivity(x) => pin.ground_sensitivity = Some(x),
+                PinStatement::Shape(x) => pin.shape = Some(x),
+                PinStatement::MustJoin(x) => pin.mustjoin = Some(x),
+                PinStatement::Antenna(x) => {
+                    pin_antenna_fold(&mut pin.pin_antenna, &mut antenna_model, x)
+                }
+                PinStatement::Property(x) => pin.property.extend(x),
+                PinStatement::Port(x) => pin.pin_port.push(x),
+            }
+        }
+        pin.comments.extend(data.3);
+        pin.comments.extend(end_comment);
+        (res, pin)
     })
 }
 
-// ITERATE syntax not supported
+fn pin_body_statement(input: &str) -> LefRes<&str, PinStatement> {
+    alt((
+        map(
+            delimited(ws(tag("TAPERRULE")), tstring, ws(tag(";"))),
+            |x| PinStatement::TaperRule(x.to_string()),
+        ),
+        map(
+            delimited(
+                ws(tag("DIRECTION")),
+                macro_pin_direction_encode,
+                ws(tag(";")),
+            ),
+            PinStatement::Direction,
+        ),
+        map(
+            delimited(ws(tag("USE")), use_type_encode, ws(tag(";"))),
+            PinStatement::Use,
+        ),
+        // kept as written, with the quotes
+        map(delimited(ws(tag("NETEXPR")), quoted, ws(tag(";"))), |x| {
+            PinStatement::NetExpr(x.to_string())
+        }),
+        map(
+            delimited(ws(tag("SUPPLYSENSITIVITY")), tstring, ws(tag(";"))),
+            |x| PinStatement::SupplySensitivity(x.to_string()),
+        ),
+        map(
+            delimited(ws(tag("GROUNDSENSITIVITY")), tstring, ws(tag(";"))),
+            |x| PinStatement::GroundSensitivity(x.to_string()),
+        ),
+        map(
+            delimited(ws(tag("SHAPE")), macro_pin_shape_encode, ws(tag(";"))),
+            PinStatement::Shape,
+        ),
+        map(delimited(ws(tag("MUSTJOIN")), tstring, ws(tag(";"))), |x| {
+            PinStatement::MustJoin(x.to_string())
+        }),
+        map(pin_antenna_statement, PinStatement::Antenna),
+        map(property_statement, PinStatement::Property),
+        map(macro_pin_port, PinStatement::Port),
+    ))(input)
+}
+
+enum PortLayerStatement {
+    Width(f64),
+    Geometry(PortGeometry),
+}
+
+// LAYER layerName [EXCEPTPGNET] [SPACING minSpacing | DESIGNRULEWIDTH value] [MASK maskNum] ;
 fn port_geometry(input: &str) -> LefRes<&str, PortShape> {
     context(
         "Macro Pin Layer Geometry Statement",
@@ -167,64 +325,148 @@ fn port_geometry(input: &str) -> LefRes<&str, PortShape> {
                 ws(tag("LAYER")),
                 tuple((
                     tstring,
-                    map(opt(ws(tag("EXCEPTPGNET"))), |x| match x {
-                        None => false,
-                        Some(_) => true,
-                    }),
+                    map(opt(ws(tag("EXCEPTPGNET"))), |x| x.is_some()),
                     opt(alt((
-                        map(preceded(tag("SPACING"), positive_number), |x| (true, x)),
-                        map(preceded(tag("DESIGNRULEWIDTH"), positive_number), |x| {
-                            (false, x)
-                        }),
+                        map(preceded(ws(tag("SPACING")), float), |x| (true, x)),
+                        map(preceded(ws(tag("DESIGNRULEWIDTH")), float), |x| (false, x)),
                     ))),
+                    opt(mask),
                 )),
                 ws(tag(";")),
             ),
             many1(alt((
-                map(delimited(ws(tag("PATH")), pt_list, ws(tag(";"))), |x| {
-                    PortGeometry::Path(x)
-                }),
-                map(delimited(ws(tag("RECT")), rect, ws(tag(";"))), |x| {
-                    PortGeometry::Rect(x)
-                }),
-                map(delimited(ws(tag("POLYGON")), pt_list, ws(tag(";"))), |x| {
-                    PortGeometry::Polygon(x)
-                }),
                 map(
-                    delimited(ws(tag("VIA")), tuple((pt, tstring)), ws(tag(";"))),
-                    |x| PortGeometry::Via((x.1.to_string(), x.0)),
+                    delimited(ws(tag("WIDTH")), float, ws(tag(";"))),
+                    PortLayerStatement::Width,
                 ),
+                map(port_geometry_item, PortLayerStatement::Geometry),
             ))),
         )),
     )(input)
     .map(|(res, data)| {
+        // a WIDTH applies to every PATH after it on the layer
+        let mut width = None;
+        let mut geometries = Vec::new();
+        for statement in data.1 {
+            match statement {
+                PortLayerStatement::Width(x) => width = Some(x),
+                PortLayerStatement::Geometry(mut x) => {
+                    if let PortGeometry::Path((_, w, _)) = &mut x {
+                        *w = width;
+                    }
+                    if let PortGeometry::Iterate((g, _)) = &mut x {
+                        if let PortGeometry::Path((_, w, _)) = g.as_mut() {
+                            *w = width;
+                        }
+                    }
+                    geometries.push(x);
+                }
+            }
+        }
         (
             res,
             PortShape {
                 layer_name: (data.0).0.to_string(),
-                geometries: data.1,
+                except_pgnet: (data.0).1,
+                min_spacing: (data.0).2,
+                mask: (data.0).3,
+                geometries,
             },
         )
     })
 }
 
-// fn port_via_geometry(input: &str) -> LefRes<&str, PortViaGeometry> {
-//     context(
-//         "Macro Pin & Obstacle Port Via Statement",
-//         delimited(ws(tag("VIA")), tuple((pt, tstring)), ws(tag(";"))),
-//     )(input)
-//     .map(|(res, data)| {
-//         (
-//             res,
-//             PortViaGeometry {
-//                 via_name: data.1.to_string(),
-//                 via_location: data.0,
-//             },
-//         )
-//     })
-// }
+// {PATH | RECT | POLYGON} [MASK maskNum] [ITERATE] ... [DO numX BY numY STEP spaceX spaceY] ;
+fn port_geometry_item(input: &str) -> LefRes<&str, PortGeometry> {
+    context(
+        "Macro Pin & Obstacle Geometry Statement",
+        map_opt(
+            terminated(
+                alt((
+                    map(
+                        tuple((
+                            preceded(ws(tag("PATH")), opt(mask)),
+                            iterate_keyword,
+                            pt_list,
+                            opt(step_pattern),
+                        )),
+                        |(mask, iterate, x, step)| {
+                            (iterate, PortGeometry::Path((x, None, mask)), step)
+                        },
+                    ),
+                    map(
+                        tuple((
+                            preceded(ws(tag("RECT")), opt(mask)),
+                            iterate_keyword,
+                            rect,
+                            opt(step_pattern),
+                        )),
+                        |(mask, iterate, x, step)| (iterate, PortGeometry::Rect((x, mask)), step),
+                    ),
+                    map(
+                        tuple((
+                            preceded(ws(tag("POLYGON")), opt(mask)),
+                            iterate_keyword,
+                            pt_list,
+                            opt(step_pattern),
+                        )),
+                        |(mask, iterate, x, step)| {
+                            (iterate, PortGeometry::Polygon((x, mask)), step)
+                        },
+                    ),
+                )),
+                ws(tag(";")),
+            ),
+            iterate_geometry,
+        ),
+    )(input)
+}
+
+// VIA [ITERATE] [MASK viaMaskNum] pt viaName [DO numX BY numY STEP spaceX spaceY] ;
+fn port_via(input: &str) -> LefRes<&str, PortGeometry> {
+    context(
+        "Macro Pin & Obstacle Via Statement",
+        map_opt(
+            terminated(
+                map(
+                    tuple((
+                        preceded(ws(tag("VIA")), iterate_keyword),
+                        opt(via_mask),
+                        pt,
+                        tstring,
+                        opt(step_pattern),
+                    )),
+                    |(iterate, mask, x, name, step)| {
+                        (
+                            iterate,
+                            PortGeometry::Via((name.to_string(), x, mask)),
+                            step,
+                        )
+                    },
+                ),
+                ws(tag(";")),
+            ),
+            iterate_geometry,
+        ),
+    )(input)
+}
+
+// ITERATE and the step pattern come together
+fn iterate_geometry(
+    (iterate, geometry, step): (bool, PortGeometry, Option<StepPattern>),
+) -> Option<PortGeometry> {
+    match (iterate, step) {
+        (true, Some(step)) => Some(PortGeometry::Iterate((Box::new(geometry), step))),
+        (false, None) => Some(geometry),
+        _ => None,
+    }
+}
 
-fn macro_pin_port(input: &str) -> LefRes<&str, (Option<u8>, Vec<PortShape>)> {
+fn iterate_keyword(input: &str) -> LefRes<&str, bool> {
+    map(opt(ws(tag("ITERATE"))), |x| x.is_some())(input)
+}
+
+fn macro_pin_port(input: &str) -> LefRes<&str, MacroPort> {
     context(
         "Macro Pin Port Statement",
         delimited(
@@ -235,112 +477,70 @@ fn macro_pin_port(input: &str) -> LefRes<&str, (Option<u8>, Vec<PortShape>)> {
                     macro_pin_port_class_encode,
                     ws(tag(";")),
                 )),
-                many1(port_geometry),
+                many1(macro_obs),
             )),
             ws(tag("END")),
         ),
     )(input)
+    .map(|(res, data)| {
+        let mut port = MacroPort {
+            port_class: data.0,
+            port_shape: Vec::new(),
+            port_via: Vec::new(),
+        };
+        for x in data.1 {
+            match x {
+                MacroObs::LayerObj(x) => port.port_shape.push(x),
+                MacroObs::ViaObj(x) => port.port_via.push(x),
+            }
+        }
+        (res, port)
+    })
 }
 
-fn obs_statement(input: &str) -> LefRes<&str, Vec<PortShape>> {
+fn obs_statement(input: &str) -> LefRes<&str, Vec<MacroObs>> {
     context(
         "Macro Obstacle Statement",
-        delimited(ws(tag("OBS")), many1(port_geometry), ws(tag("END"))),
+        delimited(ws(tag("OBS")), many1(macro_obs), ws(tag("END"))),
     )(input)
 }
 
-fn density_statement(input: &str) -> LefRes<&str, MacroDensity> {
+fn macro_obs(input: &str) -> LefRes<&str, MacroObs> {
+    alt((
+        map(port_geometry, MacroObs::LayerObj),
+        map(port_via, MacroObs::ViaObj),
+    ))(input)
+}
+
+// DENSITY { LAYER layerName ; { RECT pt pt densityValue ; } ... } ... END
+fn density_statement(input: &str) -> LefRes<&str, Vec<MacroDensity>> {
     context(
-        "
-        Macro Density Statement
-        ",
+        "Macro Density Statement",
         delimited(
             ws(tag("DENSITY")),
-            tuple((
+            many1(tuple((
                 delimited(ws(tag("LAYER")), tstring, ws(tag(";"))),
                 many1(delimited(
                     ws(tag("RECT")),
                     tuple((rect, float)),
                     ws(tag(";")),
                 )),
-            )),
+            ))),
             ws(tag("END")),
         ),
     )(input)
     .map(|(res, data)| {
         (
             res,
-            MacroDensity {
-                layer_name: data.0.to_string(),
-                rect_region: data.1,
-            },
+            data.into_iter()
+                .map(|(layer_name, rect_region)| MacroDensity {
+                    layer_name: layer_name.to_string(),
+                    rect_region,
+                })
+                .collect(),
         )
     })
 }
-// // partial checking model
-// fn pin_antenna_statement(input: &str) -> LefRes<&str, MacroPinAntenna> {
-//     context(
-//         "Macro Pin Antenna Statement",
-//         tuple((
-//             tuple((
-//                 opt(delimited(
-//                     ws(tag("ANTENNAPARTIALMETALAREA")),
-//                     float,
-//                     ws(tag(";")),
-//                 )),
-//                 opt(delimited(
-//                     ws(tag("ANTENNAPARTIALMETALSIDEAREA")),
-//                     float,
-//                     ws(tag(";")),
-//                 )),
-//                 opt(delimited(
-//                     ws(tag("ANTENNAPARTIALCUTAREA")),
-//                     float,
-//                     ws(tag(";")),
-//                 )),
-//             )),
-//             opt(delimited(ws(tag("ANTENNADIFFAREA")), float, ws(tag(";")))),
-//             opt(delimited(
-//                 ws(tag("ANTENNAMODEL")),
-//                 antenna_model_encode,
-//                 ws(tag(";")),
-//             )),
-//             opt(delimited(ws(tag("ANTENNAGATEAREA")), float, ws(tag(";")))),
-//             tuple((
-//                 opt(delimited(ws(tag("ANTENNAMAXAREACAR")), float, ws(tag(";")))),
-//                 opt(delimited(
-//                     ws(tag("ANTENNAMAXSIDEAREACAR")),
-//                     float,
-//                     ws(tag(";")),
-//                 )),
-//                 opt(delimited(ws(tag("ANTENNAMAXCUTCAR")), float, ws(tag(";")))),
-//             )),
-//         )),
-//     )(input)
-//     .map(|(res, data)| {
-//         (
-//             res,
-//             MacroPinAntenna {
-//                 partial_metal_area: (data.0).0,
-//                 partial_metal_sidearea: (data.0).1,
-//                 partial_cutarea: (data.0).2,
-//                 diffarea: data.1,
-//                 model: data.2.map_or(0, |x| x),
-//                 gatearea: data.3,
-//                 max_area_car: (data.4).0,
-//                 max_sidearea_car: (data.4).1,
-//                 max_cut_car: (data.4).2,
-//             },
-//         )
-//     })
-// }
-
-fn pin_antenna_statement(input: &str) -> LefRes<&str, ()> {
-    value(
-        (),
-        many1(ws(tuple((tag("ANTENNA"), take_until(";"), tag(";"))))),
-    )(input)
-}
 
 #[cfg(test)]
 mod tests {
@@ -444,6 +644,206 @@ mod tests {
   END A";
         let (_, _) = pin_statement(test_str).unwrap();
     }
+    #[test]
+    fn test_pin_ports() {
+        let test_str = "  PIN VSS
+    DIRECTION INOUT ;
+    USE GROUND ;
+    PORT
+      CLASS CORE ;
+      LAYER M2 ;
+        RECT 0 -0.085 0.19 0.085 ;
+    END
+    PORT
+      LAYER M2 ;
+        RECT 0 1.175 0.19 1.345 ;
+      LAYER M1 ;
+        RECT 0 1.2 0.19 1.3 ;
+    END
+  END VSS";
+        let (_, pin) = pin_statement(test_str).unwrap();
+        assert_eq!(pin.pin_port.len(), 2);
+        assert_eq!(pin.pin_port[0].port_class, Some(1));
+        assert_eq!(pin.pin_port[1].port_class, None);
+        assert_eq!(pin.pin_port[1].port_shape.len(), 2);
+    }
+
+    #[test]
+    fn test_iterate() {
+        let test_str = "  PIN VDD
+    DIRECTION INOUT ;
+    USE POWER ;
+    PORT
+      LAYER M3 ;
+        RECT ITERATE 0 0 0.2 1 DO 4 BY 2 STEP 1.0 5.0 ;
+        PATH ITERATE 0 0 0 2 DO 2 BY 1 STEP 3 0 ;
+        POLYGON ITERATE 0 0 1 0 1 1 DO 1 BY 3 STEP 0 2 ;
+        RECT 0 0 0.1 0.1 ;
+      VIA ITERATE 0.5 0.5 via12 DO 3 BY 1 STEP 0.4 0 ;
+    END
+  END VDD";
+        let (_, pin) = pin_statement(test_str).unwrap();
+        let shape = &pin.pin_port[0].port_shape[0];
+        assert_eq!(shape.geometries.len(), 4);
+        match &shape.geometries[0] {
+            PortGeometry::Iterate((x, step)) => {
+                assert!(matches!(x.as_ref(), PortGeometry::Rect(_)));
+                assert_eq!((step.num_x, step.num_y), (4, 2));
+            }
+            _ => panic!(),
+        }
+        let rects: Vec<_> = shape.geometries[0].expand().collect();
+        assert_eq!(rects.len(), 8);
+        match &rects[7] {
+            PortGeometry::Rect((x, _)) => assert_eq!(*x, ((3.0, 5.0), (3.2, 6.0))),
+            _ => panic!(),
+        }
+        assert_eq!(shape.expanded_geometries().count(), 8 + 2 + 3 + 1);
+        assert_eq!(pin.pin_port[0].port_via[0].expand().count(), 3);
+        assert!(pin_statement(&test_str.replace("RECT 0 0 0.1", "RECT ITERATE 0 0 0.1")).is_err());
+    }
+
+    #[test]
+    fn test_layer_attributes() {
+        let test_str = "OBS
+    LAYER M1 EXCEPTPGNET SPACING 0.07 ;
+      RECT MASK 2 0 0 1 1 ;
+      WIDTH 0.1 ;
+      PATH 0 0 1 0 ;
+      PATH MASK 1 ITERATE 0 0 0 1 DO 2 BY 1 STEP 0.5 0 ;
+    LAYER M2 DESIGNRULEWIDTH 0.25 ;
+      POLYGON 0 0 1 0 1 1 ;
+    VIA MASK 021 0.5 0.5 via12 ;
+  END";
+        let (_, obs) = obs_statement(test_str).unwrap();
+        assert!(matches!(
+            obs[2],
+            MacroObs::ViaObj(PortGeometry::Via((_, _, Some(0x021))))
+        ));
+        let obs: Vec<_> = obs
+            .iter()
+            .filter_map(|x| match x {
+                MacroObs::LayerObj(x) => Some(x),
+                _ => None,
+            })
+            .collect();
+        assert!(obs[0].except_pgnet);
+        assert_eq!(obs[0].min_spacing, Some((true, 0.07)));
+        assert!(matches!(
+            obs[0].geometries[0],
+            PortGeometry::Rect((_, Some(2)))
+        ));
+        assert!(matches!(
+            obs[0].geometries[1],
+            PortGeometry::Path((_, Some(w), None)) if w == 0.1
+        ));
+        match &obs[0].geometries[2] {
+            PortGeometry::Iterate((x, _)) => {
+                assert!(matches!(
+                    x.as_ref(),
+                    PortGeometry::Path((_, Some(_), Some(1)))
+                ))
+            }
+            _ => panic!(),
+        }
+        assert_eq!(obs[1].min_spacing, Some((false, 0.25)));
+    }
+
+    #[test]
+    fn test_obs_density() {
+        let test_str = "MACRO SRAM
+  CLASS BLOCK ;
+  SIZE 100 BY 50 ;
+  OBS
+    VIA 10 10 via12 ;
+    LAYER M1 DESIGNRULEWIDTH 0.3 ;
+      RECT 0 0 100 50 ;
+    VIA ITERATE 20 10 via12 DO 5 BY 1 STEP 2 0 ;
+    LAYER M2 ;
+      RECT 0 0 100 50 ;
+  END
+  PIN CLK
+    DIRECTION INPUT ;
+    PORT
+      LAYER M3 ;
+        RECT 0 0 1 1 ;
+    END
+  END CLK
+  DENSITY
+    LAYER M1 ;
+      RECT 0 0 50 50 60.5 ;
+      RECT 50 0 100 50 45 ;
+    LAYER M2 ;
+      RECT 0 0 100 50 30 ;
+  END
+END SRAM";
+        let (_, cell) = macro_parser(test_str).unwrap();
+        assert_eq!(cell.macro_obs.len(), 4);
+        assert!(matches!(cell.macro_obs[0], MacroObs::ViaObj(_)));
+        match &cell.macro_obs[1] {
+            MacroObs::LayerObj(x) => assert_eq!(x.min_spacing, Some((false, 0.3))),
+            _ => panic!(),
+        }
+        assert!(matches!(
+            cell.macro_obs[2],
+            MacroObs::ViaObj(PortGeometry::Iterate(_))
+        ));
+        assert_eq!(cell.macro_pin.len(), 1);
+        assert_eq!(cell.macro_density.len(), 2);
+        assert_eq!(cell.macro_density[0].rect_region[0].1, 60.5);
+        assert_eq!(cell.macro_density[1].layer_name, "M2");
+    }
+
+    #[test]
+    fn test_macro_statements() {
+        let test_str = "MACRO CORNER_LL
+  FOREIGN CORNER_LL 0 0 N ;
+  FOREIGN CORNER_LL_ALT -1 -1 FS ;
+  SIZE 120 BY 120 ;
+  EEQ CORNER_UL ;
+  CLASS COVER ;
+  FIXEDMASK ;
+  SOURCE USER ;
+  LEQ CORNER_LR ;
+  POWER 0.5 ;
+  SYMMETRY X Y R90 ;
+  SITE core ;
+  SITE core 0 0 N DO 10 BY 2 STEP 0.2 1.4 ;
+  SITE io 1.5 2.5 FS ;
+  OBS
+    LAYER M1 ;
+      RECT 0 0 120 120 ;
+  END
+END CORNER_LL";
+        let (_, cell) = macro_parser(test_str).unwrap();
+        assert_eq!(cell.macro_class, Some(1));
+        assert!(cell.fixed_mask);
+        assert_eq!(cell.foreign_cell.len(), 2);
+        assert_eq!(cell.foreign_cell[1].1, Some((-1.0, -1.0)));
+        assert_eq!(cell.origin, (0.0, 0.0));
+        assert_eq!(cell.macro_size, Some((120.0, 120.0)));
+        assert_eq!(cell.eeq_macro.as_deref(), Some("CORNER_UL"));
+        assert_eq!(cell.leq_macro.as_deref(), Some("CORNER_LR"));
+        assert_eq!(cell.source, Some(0));
+        assert_eq!(cell.power, Some(0.5));
+        assert_eq!(cell.macro_symmetry, vec![0, 1, 2]);
+        assert_eq!(cell.macro_site.len(), 3);
+        assert!(cell.macro_site[0].site_pattern.is_none());
+        let (origin, _, step) = cell.macro_site[1].site_pattern.as_ref().unwrap();
+        assert_eq!(*origin, (0.0, 0.0));
+        let step = step.as_ref().unwrap();
+        assert_eq!((step.num_x, step.num_y), (10, 2));
+        assert_eq!((step.space_x, step.space_y), (0.2, 1.4));
+        let (origin, _, step) = cell.macro_site[2].site_pattern.as_ref().unwrap();
+        assert_eq!(*origin, (1.5, 2.5));
+        assert!(step.is_none());
+        assert!(cell.macro_pin.is_empty());
+        assert_eq!(cell.macro_obs.len(), 1);
+
+        let (_, cell) = macro_parser("MACRO EMPTY\nEND EMPTY").unwrap();
+        assert!(cell.macro_class.is_none() && cell.macro_size.is_none());
+    }
+
     #[test]
     fn test_pin3() {
         let test_str = "  PIN B
@@ -459,6 +859,66 @@ mod tests {
         let (_, _) = pin_statement(test_str).unwrap();
     }
 
+    #[test]
+    fn test_pin_order() {
+        let test_str = "  PIN A
+    ANTENNAGATEAREA 0.159 ;
+    USE SIGNAL ;
+    NETEXPR \"power1 VDD1\" ;
+    DIRECTION OUTPUT ;
+    PORT
+      LAYER li1 ;
+        RECT 0.1 0.1 0.2 0.2 ;
+    END
+  END A";
+        let (_, pin) = pin_statement(test_str).unwrap();
+        assert_eq!(pin.direction, 2);
+        assert_eq!(pin.use_type, 0);
+        assert_eq!(pin.net_expr.as_deref(), Some("\"power1 VDD1\""));
+        let antenna = pin.pin_antenna.unwrap();
+        assert_eq!(antenna.antenna_model(0).unwrap().gate_area(None), 0.159);
+
+        // antenna statements after the PORT, no DIRECTION
+        let test_str = "  PIN B
+    PORT
+      LAYER li1 ;
+        RECT 0.1 0.1 0.2 0.2 ;
+    END
+    ANTENNADIFFAREA 0.2 ;
+  END B";
+        let (_, pin) = pin_statement(test_str).unwrap();
+        assert_eq!(pin.direction, 0);
+        assert_eq!(pin.pin_port.len(), 1);
+        assert_eq!(pin.pin_antenna.unwrap().diff_area(None), 0.2);
+
+        // ANTENNAMODEL groups around the PORT
+        let test_str = "  PIN C
+    ANTENNAGATEAREA 0.1 ;
+    ANTENNAMODEL OXIDE2 ;
+    ANTENNAGATEAREA 0.2 ;
+    DIRECTION INPUT ;
+    ANTENNAMAXAREACAR 50 LAYER met1 ;
+    PORT
+      LAYER li1 ;
+        RECT 0.1 0.1 0.2 0.2 ;
+    END
+    ANTENNAGATEAREA 0.3 LAYER met1 ;
+    ANTENNAMODEL OXIDE1 ;
+    ANTENNADIFFAREA 0.4 ;
+    ANTENNAMAXAREACAR 30 ;
+  END C";
+        let (_, pin) = pin_statement(test_str).unwrap();
+        let antenna = pin.pin_antenna.unwrap();
+        assert_eq!(antenna.diff_area(None), 0.4);
+        let oxide1 = antenna.antenna_model(0).unwrap();
+        assert_eq!(oxide1.gate_area(None), 0.1);
+        assert_eq!(oxide1.max_area_car, vec![(30.0, None)]);
+        let oxide2 = antenna.antenna_model(1).unwrap();
+        assert_eq!(oxide2.gatearea.len(), 2);
+        assert_eq!(oxide2.gate_area(Some("met1")), 0.3);
+        assert_eq!(oxide2.max_area_car, vec![(50.0, Some("met1".to_string()))]);
+    }
+
     #[test]
     fn test_macro2() {
         let test_str = "MACRO ANTENNA1_A9TL40
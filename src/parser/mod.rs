@@ -1,7 +1,15 @@
+mod antenna_parser;
 mod base;
 mod common;
 mod encoder;
 mod header_parser;
+mod layer_parser;
+mod lef58_parser;
 pub mod lef_parser;
 mod macro_parser;
+mod nondefault_parser;
+mod property_parser;
 mod site_parser;
+mod units_parser;
+mod via_parser;
+mod viarule_parser;
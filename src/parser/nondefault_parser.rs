@@ -0,0 +1,220 @@
+use crate::{
+    model::{LefVia, NonDefaultRule, NonDefaultRuleLayer, PropValue},
+    LefRes,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{map, map_opt, opt};
+use nom::error::context;
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, tuple};
+
+use super::base::{float, positive_number, tstring, ws};
+use super::property_parser::property_statement;
+use super::via_parser::via_parser;
+
+enum NonDefaultRuleStatement {
+    Layer(NonDefaultRuleLayer),
+    Via(Box<LefVia>),
+    UseVia(String),
+    UseViaRule(String),
+    MinCuts((String, u32)),
+    Property(Vec<(String, PropValue)>),
+}
+
+pub fn nondefaultrule_parser(input: &str) -> LefRes<&str, NonDefaultRule> {
+    context(
+        "NonDefaultRule Statement",
+        tuple((
+            preceded(ws(tag("NONDEFAULTRULE")), tstring),
+            map(opt(tuple((ws(tag("HARDSPACING")), ws(tag(";"))))), |x| {
+                x.is_some()
+            }),
+            many0(alt((
+                map(nondefaultrule_layer, NonDefaultRuleStatement::Layer),
+                map(via_parser, |x| NonDefaultRuleStatement::Via(Box::new(x))),
+                map(
+                    delimited(ws(tag("USEVIARULE")), tstring, ws(tag(";"))),
+                    |x| NonDefaultRuleStatement::UseViaRule(x.to_string()),
+                ),
+                map(delimited(ws(tag("USEVIA")), tstring, ws(tag(";"))), |x| {
+                    NonDefaultRuleStatement::UseVia(x.to_string())
+                }),
+                map(
+                    delimited(
+                        ws(tag("MINCUTS")),
+                        tuple((tstring, positive_number)),
+                        ws(tag(";")),
+                    ),
+                    |x| NonDefaultRuleStatement::MinCuts((x.0.to_string(), x.1)),
+                ),
+                map(property_statement, NonDefaultRuleStatement::Property),
+            ))),
+            preceded(ws(tag("END")), tstring),
+        )),
+    )(input)
+    .map(|(res, data)| {
+        let mut rule = NonDefaultRule {
+            rule_name: data.0.to_string(),
+            hard_spacing: data.1,
+            rule_layer: Vec::new(),
+            via: Vec::new(),
+            use_via: Vec::new(),
+            use_viarule: Vec::new(),
+            min_cuts: Vec::new(),
+            property: Vec::new(),
+            source_file: None,
+            comments: Vec::new(),
+        };
+        for statement in data.2 {
+            match statement {
+                NonDefaultRuleStatement::Layer(x) => rule.rule_layer.push(x),
+                NonDefaultRuleStatement::Via(x) => rule.via.push(*x),
+                NonDefaultRuleStatement::UseVia(x) => rule.use_via.push(x),
+                NonDefaultRuleStatement::UseViaRule(x) => rule.use_viarule.push(x),
+                NonDefaultRuleStatement::MinCuts(x) => rule.min_cuts.push(x),
+                NonDefaultRuleStatement::Property(x) => rule.property.extend(x),
+            }
+        }
+        (res, rule)
+    })
+}
+
+enum NonDefaultRuleLayerStatement {
+    Width(f64),
+    DiagWidth(f64),
+    Spacing(f64),
+    WireExtension(f64),
+    Resistance(f64),
+    Capacitance(f64),
+    EdgeCapacitance(f64),
+}
+
+// statements may come in any order, WIDTH is required
+fn nondefaultrule_layer(input: &str) -> LefRes<&str, NonDefaultRuleLayer> {
+    context(
+        "NonDefaultRule Layer Statement",
+        map_opt(
+            tuple((
+                preceded(ws(tag("LAYER")), tstring),
+                many0(alt((
+                    map(
+                        delimited(ws(tag("WIDTH")), float, ws(tag(";"))),
+                        NonDefaultRuleLayerStatement::Width,
+                    ),
+                    map(
+                        delimited(ws(tag("DIAGWIDTH")), float, ws(tag(";"))),
+                        NonDefaultRuleLayerStatement::DiagWidth,
+                    ),
+                    map(
+                        delimited(ws(tag("SPACING")), float, ws(tag(";"))),
+                        NonDefaultRuleLayerStatement::Spacing,
+                    ),
+                    map(
+                        delimited(ws(tag("WIREEXTENSION")), float, ws(tag(";"))),
+                        NonDefaultRuleLayerStatement::WireExtension,
+                    ),
+                    map(
+                        delimited(
+                            tuple((ws(tag("RESISTANCE")), ws(tag("RPERSQ")))),
+                            float,
+                            ws(tag(";")),
+                        ),
+                        NonDefaultRuleLayerStatement::Resistance,
+                    ),
+                    map(
+                        delimited(
+                            tuple((ws(tag("CAPACITANCE")), ws(tag("CPERSQDIST")))),
+                            float,
+                            ws(tag(";")),
+                        ),
+                        NonDefaultRuleLayerStatement::Capacitance,
+                    ),
+                    map(
+                        delimited(ws(tag("EDGECAPACITANCE")), float, ws(tag(";"))),
+                        NonDefaultRuleLayerStatement::EdgeCapacitance,
+                    ),
+                ))),
+                preceded(ws(tag("END")), tstring),
+            )),
+            |data| {
+                let mut width = None;
+                let mut layer = NonDefaultRuleLayer {
+                    layer_name: data.0.to_string(),
+                    width: 0.0,
+                    diag_width: None,
+                    spacing: None,
+                    wire_extension: None,
+                    resistance: None,
+                    capacitance: None,
+                    edge_capacitance: None,
+                };
+                for statement in data.1 {
+                    match statement {
+                        NonDefaultRuleLayerStatement::Width(x) => width = Some(x),
+                        NonDefaultRuleLayerStatement::DiagWidth(x) => layer.diag_width = Some(x),
+                        NonDefaultRuleLayerStatement::Spacing(x) => layer.spacing = Some(x),
+                        NonDefaultRuleLayerStatement::WireExtension(x) => {
+                            layer.wire_extension = Some(x)
+                        }
+                        NonDefaultRuleLayerStatement::Resistance(x) => layer.resistance = Some(x),
+                        NonDefaultRuleLayerStatement::Capacitance(x) => layer.capacitance = Some(x),
+                        NonDefaultRuleLayerStatement::EdgeCapacitance(x) => {
+                            layer.edge_capacitance = Some(x)
+                        }
+                    }
+                }
+                layer.width = width?;
+                Some(layer)
+            },
+        ),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_nondefaultrule() {
+        let test_str = "NONDEFAULTRULE wide_clk
+  HARDSPACING ;
+  LAYER metal1
+    WIDTH 0.14 ;
+    SPACING 0.14 ;
+  END metal1
+  LAYER metal2
+    WIREEXTENSION 0.07 ;
+    RESISTANCE RPERSQ 0.2 ;
+    WIDTH 0.14 ;
+    CAPACITANCE CPERSQDIST 0.00012 ;
+    EDGECAPACITANCE 0.00003 ;
+  END metal2
+  VIA via12_wide
+    LAYER metal1 ;
+      RECT -0.1 -0.1 0.1 0.1 ;
+    LAYER via1 ;
+      RECT -0.05 -0.05 0.05 0.05 ;
+    LAYER metal2 ;
+      RECT -0.1 -0.1 0.1 0.1 ;
+  END via12_wide
+  USEVIA via12_4 ;
+  USEVIARULE M2_M1 ;
+  MINCUTS via1 2 ;
+END wide_clk";
+        let (_, rule) = nondefaultrule_parser(test_str).unwrap();
+        assert!(rule.hard_spacing);
+        assert_eq!(rule.rule_layer.len(), 2);
+        assert_eq!(rule.rule_layer[0].spacing, Some(0.14));
+        assert_eq!(rule.rule_layer[1].wire_extension, Some(0.07));
+        assert_eq!(rule.rule_layer[1].width, 0.14);
+        assert_eq!(rule.rule_layer[1].resistance, Some(0.2));
+        assert_eq!(rule.rule_layer[1].edge_capacitance, Some(0.00003));
+        assert_eq!(rule.via[0].via_name, "via12_wide");
+        assert_eq!(rule.use_via, vec!["via12_4"]);
+        assert_eq!(rule.use_viarule, vec!["M2_M1"]);
+        assert_eq!(rule.min_cuts, vec![("via1".to_string(), 2)]);
+
+        // WIDTH is required
+        assert!(nondefaultrule_layer("LAYER metal1 SPACING 0.1 ; END metal1").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    model::{PropValue, PropertyDefinition},
+    LefRes,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{map, opt};
+use nom::error::context;
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, preceded, tuple};
+
+use super::base::{float, qstring, tstring, ws};
+use super::encoder::{property_object_type_encode, property_type_encode};
+
+pub fn property_definitions_parser(input: &str) -> LefRes<&str, Vec<PropertyDefinition>> {
+    context(
+        "Property Definitions Statement",
+        delimited(
+            ws(tag("PROPERTYDEFINITIONS")),
+            many0(property_definition),
+            tuple((ws(tag("END")), ws(tag("PROPERTYDEFINITIONS")))),
+        ),
+    )(input)
+}
+
+// objectType propName propType [RANGE min max] [value | "stringValue"] ;
+fn property_definition(input: &str) -> LefRes<&str, PropertyDefinition> {
+    context(
+        "Property Definition Statement",
+        tuple((
+            property_object_type_encode,
+            tstring,
+            property_type_encode,
+            opt(preceded(ws(tag("RANGE")), tuple((float, float)))),
+            opt(prop_value),
+            ws(tag(";")),
+        )),
+    )(input)
+    .map(|(res, data)| {
+        let prop_type = data.2;
+        (
+            res,
+            PropertyDefinition {
+                object_type: data.0,
+                prop_name: data.1.to_string(),
+                prop_type,
+                range: data.3,
+                // the default value has the type of its own definition
+                value: data.4.map(|x| x.convert(prop_type).unwrap_or(x)),
+            },
+        )
+    })
+}
+
+// PROPERTY propName propVal [propName propVal] ... ;
+pub fn property_statement(input: &str) -> LefRes<&str, Vec<(String, PropValue)>> {
+    context(
+        "Property Statement",
+        delimited(
+            ws(tag("PROPERTY")),
+            many1(tuple((tstring, prop_value))),
+            ws(tag(";")),
+        ),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            data.into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    })
+}
+
+// a quoted value is a string. Numbers are kept as written, they get their type from the
+// definition once the whole library is read.
+fn prop_value(input: &str) -> LefRes<&str, PropValue> {
+    alt((
+        map(qstring, PropValue::String),
+        map(tstring, |x| {
+            let numeric = x.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c));
+            if numeric && x.parse::<f64>().is_ok() {
+                PropValue::Number(x.to_string())
+            } else {
+                PropValue::String(x.to_string())
+            }
+        }),
+    ))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_property_definitions() {
+        let test_str = "PROPERTYDEFINITIONS
+  LIBRARY libVersion STRING \"2.1\" ;
+  LAYER LEF58_TYPE STRING ;
+  MACRO area REAL RANGE 0 100 ;
+  PIN slack INTEGER ;
+END PROPERTYDEFINITIONS";
+        let (_, defs) = property_definitions_parser(test_str).unwrap();
+        assert_eq!(defs.len(), 4);
+        assert_eq!(defs[0].object_type, 0);
+        assert_eq!(defs[0].value, Some(PropValue::String("2.1".to_string())));
+        assert_eq!(defs[2].prop_type, 1);
+        assert_eq!(defs[2].range, Some((0.0, 100.0)));
+
+        let (_, props) =
+            property_statement("PROPERTY area 12.5 slack 3 LEF58_TYPE \"TYPE NWELL ;\" ;").unwrap();
+        assert_eq!(props[0].1, PropValue::Number("12.5".to_string()));
+        assert_eq!(props[1].1, PropValue::Number("3".to_string()));
+        assert_eq!(props[2].1, PropValue::String("TYPE NWELL ;".to_string()));
+    }
+}
@@ -1,15 +1,23 @@
 // use super::encoder::{net_pattern_encode, source_type_encode, use_mode_encode};
 use crate::{model::LefSite, LefRes};
-use nom::branch::{alt, permutation};
+use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::combinator::{map, opt};
+use nom::combinator::{map, map_opt};
 use nom::error::context;
-use nom::multi::many1;
-use nom::sequence::{delimited, separated_pair, tuple};
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, preceded, separated_pair, terminated, tuple};
 
 use super::base::{float, tstring, ws};
 
 use super::encoder::orient_encode;
+
+enum SiteStatement {
+    Class(bool),
+    Size((f64, f64)),
+    Symmetry(Vec<u8>),
+    RowPattern(Vec<(String, u8)>),
+}
+
 fn site_rowpattern(input: &str) -> LefRes<&str, (&str, u8)> {
     context(
         "Site Row Pattern Statement",
@@ -20,57 +28,97 @@ fn site_rowpattern(input: &str) -> LefRes<&str, (&str, u8)> {
 fn site_symmetry(input: &str) -> LefRes<&str, u8> {
     context(
         "Site Symmetry Statement",
-        alt((
+        ws(alt((
             map(tag("X"), |_| 0),
             map(tag("Y"), |_| 1),
             map(tag("R90"), |_| 2),
-        )),
+        ))),
     )(input)
 }
 
+// CLASS and SIZE are required, every statement may come in any order
 pub fn site_parser(input: &str) -> LefRes<&str, LefSite> {
     context(
         "Site Statement",
-        delimited(
-            ws(tag("SITE")),
-            permutation((
-                tstring,
-                delimited(
-                    ws(tag("CLASS")),
-                    alt((map(tag("PAD"), |_| true), map(tag("CORE"), |_| false))),
-                    ws(tag(";")),
-                ),
-                delimited(
-                    ws(tag("SIZE")),
-                    separated_pair(float, tag("BY"), float),
-                    ws(tag(";")),
-                ),
-                opt(delimited(
-                    ws(tag("SYMMETRY")),
-                    many1(site_symmetry),
-                    ws(tag(";")),
+        map_opt(
+            terminated(
+                tuple((
+                    preceded(ws(tag("SITE")), tstring),
+                    many0(alt((
+                        map(
+                            delimited(
+                                ws(tag("CLASS")),
+                                alt((map(tag("PAD"), |_| true), map(tag("CORE"), |_| false))),
+                                ws(tag(";")),
+                            ),
+                            SiteStatement::Class,
+                        ),
+                        map(
+                            delimited(
+                                ws(tag("SIZE")),
+                                separated_pair(float, ws(tag("BY")), float),
+                                ws(tag(";")),
+                            ),
+                            SiteStatement::Size,
+                        ),
+                        map(
+                            delimited(ws(tag("SYMMETRY")), many1(site_symmetry), ws(tag(";"))),
+                            SiteStatement::Symmetry,
+                        ),
+                        map(
+                            delimited(ws(tag("ROWPATTERN")), many1(site_rowpattern), ws(tag(";"))),
+                            |x| {
+                                SiteStatement::RowPattern(
+                                    x.iter().map(|y| (y.0.to_string(), y.1)).collect(),
+                                )
+                            },
+                        ),
+                    ))),
                 )),
-                opt(delimited(
-                    ws(tag("ROWPATTERN")),
-                    many1(site_rowpattern),
-                    ws(tag(";")),
-                )),
-            )),
-            tuple((ws(tag("END")), tstring)),
+                tuple((ws(tag("END")), tstring)),
+            ),
+            |(name, statements)| {
+                let mut site_class = None;
+                let mut site_size = None;
+                let mut site_symmetry = None;
+                let mut row_pattern = None;
+                for statement in statements {
+                    match statement {
+                        SiteStatement::Class(x) => site_class = Some(x),
+                        SiteStatement::Size(x) => site_size = Some(x),
+                        SiteStatement::Symmetry(x) => site_symmetry = Some(x),
+                        SiteStatement::RowPattern(x) => row_pattern = Some(x),
+                    }
+                }
+                Some(LefSite {
+                    site_name: name.to_string(),
+                    site_class: site_class?,
+                    site_symmetry,
+                    row_pattern,
+                    site_size: site_size?,
+                    source_file: None,
+                    comments: Vec::new(),
+                })
+            },
         ),
     )(input)
-    .map(|(res, data)| {
-        (
-            res,
-            LefSite {
-                site_name: data.0.to_string(),
-                site_class: data.1,
-                site_symmetry: data.3,
-                row_pattern: data
-                    .4
-                    .map(|x| x.iter().map(|y| (y.0.to_string(), y.1)).collect()),
-                site_size: data.2,
-            },
-        )
-    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_site() {
+        let test_str = "SITE core
+  SIZE 0.2 BY 1.4 ;
+  SYMMETRY X Y ;
+  CLASS CORE ;
+END core";
+        let (_, site) = site_parser(test_str).unwrap();
+        assert!(!site.site_class);
+        assert_eq!(site.site_size, (0.2, 1.4));
+        assert_eq!(site.site_symmetry, Some(vec![0, 1]));
+
+        assert!(site_parser("SITE io\n  CLASS PAD ;\nEND io").is_err());
+    }
 }
@@ -0,0 +1,116 @@
+use crate::{model::LefUnits, LefRes};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::map;
+use nom::error::context;
+use nom::multi::many0;
+use nom::sequence::{delimited, tuple};
+
+use super::base::{float, positive_number, ws};
+
+enum UnitsStatement {
+    Time(f64),
+    Capacitance(f64),
+    Resistance(f64),
+    Power(f64),
+    Current(f64),
+    Voltage(f64),
+    Database(u32),
+    Frequency(f64),
+}
+
+pub fn units_parser(input: &str) -> LefRes<&str, LefUnits> {
+    context(
+        "Units Statement",
+        delimited(
+            ws(tag("UNITS")),
+            many0(alt((
+                map(units_factor("TIME", "NANOSECONDS"), UnitsStatement::Time),
+                map(
+                    units_factor("CAPACITANCE", "PICOFARADS"),
+                    UnitsStatement::Capacitance,
+                ),
+                map(
+                    units_factor("RESISTANCE", "OHMS"),
+                    UnitsStatement::Resistance,
+                ),
+                map(units_factor("POWER", "MILLIWATTS"), UnitsStatement::Power),
+                map(
+                    units_factor("CURRENT", "MILLIAMPS"),
+                    UnitsStatement::Current,
+                ),
+                map(units_factor("VOLTAGE", "VOLTS"), UnitsStatement::Voltage),
+                map(
+                    delimited(
+                        tuple((ws(tag("DATABASE")), ws(tag("MICRONS")))),
+                        positive_number,
+                        ws(tag(";")),
+                    ),
+                    UnitsStatement::Database,
+                ),
+                map(
+                    units_factor("FREQUENCY", "MEGAHERTZ"),
+                    UnitsStatement::Frequency,
+                ),
+            ))),
+            tuple((ws(tag("END")), ws(tag("UNITS")))),
+        ),
+    )(input)
+    .map(|(res, data)| {
+        let mut units = LefUnits {
+            time: None,
+            capacitance: None,
+            resistance: None,
+            power: None,
+            current: None,
+            voltage: None,
+            database: None,
+            frequency: None,
+        };
+        for statement in data {
+            match statement {
+                UnitsStatement::Time(x) => units.time = Some(x),
+                UnitsStatement::Capacitance(x) => units.capacitance = Some(x),
+                UnitsStatement::Resistance(x) => units.resistance = Some(x),
+                UnitsStatement::Power(x) => units.power = Some(x),
+                UnitsStatement::Current(x) => units.current = Some(x),
+                UnitsStatement::Voltage(x) => units.voltage = Some(x),
+                UnitsStatement::Database(x) => units.database = Some(x),
+                UnitsStatement::Frequency(x) => units.frequency = Some(x),
+            }
+        }
+        (res, units)
+    })
+}
+
+fn units_factor<'a>(
+    name: &'static str,
+    unit: &'static str,
+) -> impl FnMut(&'a str) -> LefRes<&'a str, f64> {
+    delimited(tuple((ws(tag(name)), ws(tag(unit)))), float, ws(tag(";")))
+}
+
+pub fn manufacturing_grid(input: &str) -> LefRes<&str, f64> {
+    context(
+        "Manufacturing Grid Statement",
+        delimited(ws(tag("MANUFACTURINGGRID")), float, ws(tag(";"))),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_units() {
+        let test_str = "UNITS
+  DATABASE MICRONS 2000 ;
+  TIME NANOSECONDS 1 ;
+  CAPACITANCE PICOFARADS 1 ;
+  RESISTANCE OHMS 1 ;
+END UNITS";
+        let (_, units) = units_parser(test_str).unwrap();
+        assert_eq!(units.database, Some(2000));
+        assert_eq!(units.time, Some(1.0));
+        assert_eq!(units.voltage, None);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{
+    model::{LefVia, PortGeometry, PortShape, ViaRuleParams},
+    LefRes,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{map, opt};
+use nom::error::context;
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, preceded, tuple};
+
+use super::base::{float, positive_number, tstring, ws};
+use super::common::{mask, pt, pt_list, rect};
+use super::property_parser::property_statement;
+
+// fixed via, or a via generated from a VIARULE with the given parameters
+pub fn via_parser(input: &str) -> LefRes<&str, LefVia> {
+    context(
+        "Via Statement",
+        tuple((
+            preceded(ws(tag("VIA")), tstring),
+            map(opt(ws(tag("DEFAULT"))), |x| x.is_some()),
+            alt((
+                map(via_rule_params, |x| (None, Vec::new(), Some(x))),
+                map(
+                    tuple((
+                        opt(delimited(ws(tag("RESISTANCE")), float, ws(tag(";")))),
+                        many0(via_layer_geometry),
+                    )),
+                    |(resistance, via_layer)| (resistance, via_layer, None),
+                ),
+            )),
+            many0(property_statement),
+            preceded(ws(tag("END")), tstring),
+        )),
+    )(input)
+    .map(|(res, data)| {
+        let (resistance, via_layer, via_rule) = data.2;
+        (
+            res,
+            LefVia {
+                via_name: data.0.to_string(),
+                is_default: data.1,
+                resistance,
+                via_layer: match &via_rule {
+                    Some(x) => x.via_layer(),
+                    None => via_layer,
+                },
+                via_rule,
+                property: data.3.into_iter().flatten().collect(),
+                source_file: None,
+                comments: Vec::new(),
+            },
+        )
+    })
+}
+
+// VIARULE viaRuleName ; CUTSIZE xSize ySize ; LAYERS botMetalLayer cutLayer topMetalLayer ;
+// CUTSPACING xCutSpacing yCutSpacing ; ENCLOSURE xBotEnc yBotEnc xTopEnc yTopEnc ;
+// [ROWCOL numCutRows numCutCols ;] [ORIGIN xOffset yOffset ;]
+// [OFFSET xBotOffset yBotOffset xTopOffset yTopOffset ;] [PATTERN cutPattern ;]
+fn via_rule_params(input: &str) -> LefRes<&str, ViaRuleParams> {
+    context(
+        "Via Rule Parameters",
+        tuple((
+            delimited(ws(tag("VIARULE")), tstring, ws(tag(";"))),
+            delimited(ws(tag("CUTSIZE")), pt, ws(tag(";"))),
+            delimited(
+                ws(tag("LAYERS")),
+                tuple((tstring, tstring, tstring)),
+                ws(tag(";")),
+            ),
+            delimited(ws(tag("CUTSPACING")), pt, ws(tag(";"))),
+            delimited(ws(tag("ENCLOSURE")), tuple((pt, pt)), ws(tag(";"))),
+            opt(delimited(
+                ws(tag("ROWCOL")),
+                tuple((positive_number, positive_number)),
+                ws(tag(";")),
+            )),
+            opt(delimited(ws(tag("ORIGIN")), pt, ws(tag(";")))),
+            opt(delimited(ws(tag("OFFSET")), tuple((pt, pt)), ws(tag(";")))),
+            opt(delimited(ws(tag("PATTERN")), tstring, ws(tag(";")))),
+        )),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            ViaRuleParams {
+                rule_name: data.0.to_string(),
+                cut_size: data.1,
+                layers: (
+                    (data.2).0.to_string(),
+                    (data.2).1.to_string(),
+                    (data.2).2.to_string(),
+                ),
+                cut_spacing: data.3,
+                enclosure: data.4,
+                row_col: data.5.unwrap_or((1, 1)),
+                origin: data.6.unwrap_or((0.0, 0.0)),
+                offset: data.7.unwrap_or(((0.0, 0.0), (0.0, 0.0))),
+                pattern: data.8.map(|x| x.to_string()),
+            },
+        )
+    })
+}
+
+fn via_layer_geometry(input: &str) -> LefRes<&str, PortShape> {
+    context(
+        "Via Layer Geometry Statement",
+        tuple((
+            delimited(ws(tag("LAYER")), tstring, ws(tag(";"))),
+            many1(alt((
+                map(
+                    delimited(ws(tag("RECT")), tuple((opt(mask), rect)), ws(tag(";"))),
+                    |(mask, x)| PortGeometry::Rect((x, mask)),
+                ),
+                map(
+                    delimited(
+                        ws(tag("POLYGON")),
+                        tuple((opt(mask), pt_list)),
+                        ws(tag(";")),
+                    ),
+                    |(mask, x)| PortGeometry::Polygon((x, mask)),
+                ),
+            ))),
+        )),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            PortShape {
+                layer_name: data.0.to_string(),
+                except_pgnet: false,
+                min_spacing: None,
+                mask: None,
+                geometries: data.1,
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_via() {
+        let test_str = "VIA via1_4 DEFAULT
+  RESISTANCE 4.5 ;
+  LAYER metal1 ;
+    RECT -0.065 -0.035 0.065 0.035 ;
+  LAYER via1 ;
+    RECT -0.035 -0.035 0.035 0.035 ;
+  LAYER metal2 ;
+    POLYGON -0.035 -0.065 0.035 -0.065 0.035 0.065 -0.035 0.065 ;
+END via1_4";
+        let (_, via) = via_parser(test_str).unwrap();
+        assert_eq!(via.via_name, "via1_4");
+        assert!(via.is_default);
+        assert_eq!(via.resistance, Some(4.5));
+        assert_eq!(via.via_layer.len(), 3);
+        assert_eq!(via.via_layer[2].layer_name, "metal2");
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{
+    model::{LefViaRule, Rect, ViaRuleLayer},
+    LefRes,
+};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{map, opt};
+use nom::error::context;
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, preceded, separated_pair, tuple};
+
+use super::base::{float, tstring, ws};
+use super::common::rect;
+use super::encoder::layer_direction_encode;
+use super::property_parser::property_statement;
+
+enum ViaRuleLayerStatement {
+    Direction(u8),
+    Enclosure((f64, f64)),
+    Width((f64, f64)),
+    Rect(Rect),
+    Spacing((f64, f64)),
+    Resistance(f64),
+}
+
+pub fn viarule_parser(input: &str) -> LefRes<&str, LefViaRule> {
+    context(
+        "ViaRule Statement",
+        tuple((
+            preceded(ws(tag("VIARULE")), tstring),
+            map(opt(ws(tag("GENERATE"))), |x| x.is_some()),
+            map(opt(ws(tag("DEFAULT"))), |x| x.is_some()),
+            many1(viarule_layer),
+            many0(delimited(ws(tag("VIA")), tstring, ws(tag(";")))),
+            many0(property_statement),
+            preceded(ws(tag("END")), tstring),
+        )),
+    )(input)
+    .map(|(res, data)| {
+        (
+            res,
+            LefViaRule {
+                rule_name: data.0.to_string(),
+                is_generate: data.1,
+                is_default: data.2,
+                rule_layer: data.3,
+                via_name: data.4.iter().map(|x| x.to_string()).collect(),
+                property: data.5.into_iter().flatten().collect(),
+                source_file: None,
+                comments: Vec::new(),
+            },
+        )
+    })
+}
+
+fn viarule_layer(input: &str) -> LefRes<&str, ViaRuleLayer> {
+    context(
+        "ViaRule Layer Statement",
+        tuple((
+            delimited(ws(tag("LAYER")), tstring, ws(tag(";"))),
+            many0(alt((
+                map(
+                    delimited(ws(tag("DIRECTION")), layer_direction_encode, ws(tag(";"))),
+                    ViaRuleLayerStatement::Direction,
+                ),
+                map(
+                    delimited(ws(tag("ENCLOSURE")), tuple((float, float)), ws(tag(";"))),
+                    ViaRuleLayerStatement::Enclosure,
+                ),
+                map(
+                    delimited(
+                        ws(tag("WIDTH")),
+                        separated_pair(float, ws(tag("TO")), float),
+                        ws(tag(";")),
+                    ),
+                    ViaRuleLayerStatement::Width,
+                ),
+                map(
+                    delimited(ws(tag("RECT")), rect, ws(tag(";"))),
+                    ViaRuleLayerStatement::Rect,
+                ),
+                map(
+                    delimited(
+                        ws(tag("SPACING")),
+                        separated_pair(float, ws(tag("BY")), float),
+                        ws(tag(";")),
+                    ),
+                    ViaRuleLayerStatement::Spacing,
+                ),
+                map(
+                    delimited(ws(tag("RESISTANCE")), float, ws(tag(";"))),
+                    ViaRuleLayerStatement::Resistance,
+                ),
+            ))),
+        )),
+    )(input)
+    .map(|(res, data)| {
+        let mut layer = ViaRuleLayer {
+            layer_name: data.0.to_string(),
+            direction: None,
+            enclosure: None,
+            width: None,
+            cut_rect: None,
+            cut_spacing: None,
+            resistance: None,
+        };
+        for statement in data.1 {
+            match statement {
+                ViaRuleLayerStatement::Direction(x) => layer.direction = Some(x),
+                ViaRuleLayerStatement::Enclosure(x) => layer.enclosure = Some(x),
+                ViaRuleLayerStatement::Width(x) => layer.width = Some(x),
+                ViaRuleLayerStatement::Rect(x) => layer.cut_rect = Some(x),
+                ViaRuleLayerStatement::Spacing(x) => layer.cut_spacing = Some(x),
+                ViaRuleLayerStatement::Resistance(x) => layer.resistance = Some(x),
+            }
+        }
+        (res, layer)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_viarule() {
+        let test_str = "VIARULE M2_M1 GENERATE DEFAULT
+  LAYER metal1 ;
+    ENCLOSURE 0 0.035 ;
+  LAYER metal2 ;
+    ENCLOSURE 0 0.035 ;
+    WIDTH 0.07 TO 10 ;
+  LAYER via1 ;
+    RECT -0.035 -0.035 0.035 0.035 ;
+    SPACING 0.16 BY 0.16 ;
+    RESISTANCE 5 ;
+END M2_M1";
+        let (_, rule) = viarule_parser(test_str).unwrap();
+        assert!(rule.is_generate && rule.is_default);
+        assert_eq!(rule.rule_layer.len(), 3);
+        assert_eq!(rule.rule_layer[1].width, Some((0.07, 10.0)));
+        assert_eq!(rule.rule_layer[2].cut_spacing, Some((0.16, 0.16)));
+
+        let test_str = "VIARULE TURNM1
+  LAYER metal1 ;
+    DIRECTION VERTICAL ;
+  LAYER metal2 ;
+    DIRECTION HORIZONTAL ;
+    WIDTH 0.1 TO 2 ;
+  VIA via12_H ;
+  VIA via12_V ;
+END TURNM1";
+        let (_, rule) = viarule_parser(test_str).unwrap();
+        assert!(!rule.is_generate);
+        assert_eq!(rule.via_name, vec!["via12_H", "via12_V"]);
+    }
+}